@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use slurp::boxes::{self, parse_box};
+use slurp::format::{self, FormatContext, DEFAULT_LABELED_FORMAT};
+
+fuzz_target!(|data: &[u8]| {
+    // Every accepted box has to come back unchanged when printed the way -r expects it
+    for b in boxes::read_boxes(data) {
+        let (x, y, width, height) = b.rect;
+        let ctx = FormatContext {
+            x: x as i32,
+            y: y as i32,
+            width: width as i32,
+            height: height as i32,
+            output_x: 0,
+            output_y: 0,
+            output_scale: 1.0,
+            output_name: None,
+            label: b.label.as_deref(),
+            app_id: None,
+            title: None,
+        };
+        let line = format::expand(&format::parse(DEFAULT_LABELED_FORMAT), &ctx);
+        assert_eq!(parse_box(&line).as_ref(), Ok(&b));
+    }
+});
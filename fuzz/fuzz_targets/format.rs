@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use slurp::format::{self, FormatContext, FormatOptions};
+
+fuzz_target!(
+    |data: (&str, f64, f64, f64, f64, bool, bool, i32, i32, f64, &str)| {
+        let (format, x, y, width, height, float, pixels, output_x, output_y, output_scale, label) = data;
+        let ctx = FormatContext {
+            x: x as i32,
+            y: y as i32,
+            width: width as i32,
+            height: height as i32,
+            output_x,
+            output_y,
+            output_scale,
+            output_name: Some("DP-1"),
+            label: Some(label),
+            app_id: None,
+            title: None,
+        };
+        let tokens = format::parse(format);
+        format::expand(&tokens, &ctx);
+        format::expand_with(&tokens, &ctx, (x, y, width, height), FormatOptions { float, pixels });
+        format::expand_for_shell(&tokens);
+        format::shell_variables(&ctx);
+        assert_eq!(format::parse(&format::unparse(&tokens)), tokens);
+    }
+);
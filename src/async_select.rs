@@ -0,0 +1,61 @@
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use tokio::io::unix::AsyncFd;
+use tokio::time::Instant;
+use wayland_client::backend::WaylandError;
+use wayland_client::DispatchError;
+
+use crate::{setup, Callbacks, CandidateBox, Error, Options, Rect, Selection};
+
+// The connection's socket, registered with the tokio reactor while the selection runs
+struct ConnectionFd(RawFd);
+
+impl AsRawFd for ConnectionFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+pub async fn select_async(options: impl Into<Options>) -> Result<Option<Rect>, Error> {
+    Ok(select_with_callbacks_async(options.into(), Vec::new(), Callbacks::default()).await?.into_iter().next().map(|s| s.rect))
+}
+
+// Same as select_with_callbacks, but waits for compositor events on the tokio reactor instead of
+// blocking the thread. Setting up the surfaces still takes a couple of short roundtrips.
+pub async fn select_with_callbacks_async(options: Options, boxes: Vec<CandidateBox>, callbacks: Callbacks) -> Result<Vec<Selection>, Error> {
+    let (conn, mut event_queue, mut state) = setup(options, boxes, callbacks, true)?;
+    let fd = AsyncFd::new(ConnectionFd(conn.backend().poll_fd().as_raw_fd())).map_err(Error::Poll)?;
+    let deadline = state.options.timeout.map(|timeout| Instant::now() + timeout);
+
+    while state.running {
+        event_queue.dispatch_pending(&mut state)?;
+        if !state.running {
+            break;
+        }
+        event_queue.flush().map_err(DispatchError::Backend)?;
+
+        // Events may have been queued by another reader since dispatching, in which case there's no guard
+        if let Some(guard) = event_queue.prepare_read() {
+            let readable = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, fd.readable()).await {
+                    Ok(readable) => readable,
+                    Err(_) => {
+                        state.fail(Error::Timeout);
+                        break;
+                    }
+                },
+                None => fd.readable().await,
+            };
+            let mut ready = readable.map_err(Error::Poll)?;
+            // Readiness is edge-triggered, it may only be cleared once the socket was drained
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => ready.clear_ready(),
+                Err(e) => return Err(DispatchError::Backend(e).into()),
+            }
+        }
+    }
+    drop(fd);
+    state.take_results()
+}
@@ -0,0 +1,99 @@
+use std::io::BufRead;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateBox {
+    pub rect: (f64, f64, f64, f64),
+    pub label: Option<String>,
+    // Set by window providers
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+}
+
+impl CandidateBox {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (bx, by, bw, bh) = self.rect;
+        x >= bx && y >= by && x < bx + bw && y < by + bh
+    }
+}
+
+// Parses a single "x,y WxH [label]" line
+pub fn parse_box(line: &str) -> Result<CandidateBox, String> {
+    let invalid = || format!("invalid box '{}', expected 'x,y WxH [label]'", line);
+    let mut parts = line.trim().splitn(3, char::is_whitespace);
+    let pos = parts.next().ok_or_else(invalid)?;
+    let size = parts.next().ok_or_else(invalid)?;
+    let label = parts.next().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string);
+
+    let (x, y) = pos.split_once(',').ok_or_else(invalid)?;
+    let (w, h) = size.split_once('x').ok_or_else(invalid)?;
+
+    let num = |s: &str| s.trim().parse::<i32>().map_err(|_| invalid());
+    let (x, y, w, h) = (num(x)?, num(y)?, num(w)?, num(h)?);
+    if w <= 0 || h <= 0 {
+        return Err(invalid());
+    }
+    Ok(CandidateBox { rect: (x as f64, y as f64, w as f64, h as f64), label, app_id: None, title: None })
+}
+
+// A region without a label, as --initial and the coordinate prompt take it
+pub fn parse_rect(s: &str) -> Result<(f64, f64, f64, f64), String> {
+    let b = parse_box(s)?;
+    if b.label.is_some() {
+        return Err(format!("invalid region '{}', expected 'x,y WxH'", s));
+    }
+    Ok(b.rect)
+}
+
+// A size such as --size and --min-size take
+pub fn parse_size(s: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("invalid size '{}', expected WxH", s);
+    let (w, h) = s.split_once('x').ok_or_else(invalid)?;
+    let w: u32 = w.trim().parse().map_err(|_| invalid())?;
+    let h: u32 = h.trim().parse().map_err(|_| invalid())?;
+    if w == 0 || h == 0 {
+        return Err(invalid());
+    }
+    Ok((w as f64, h as f64))
+}
+
+// Reads boxes one per line, skipping blank lines and reporting invalid ones
+pub fn read_boxes(reader: impl BufRead) -> Vec<CandidateBox> {
+    let mut boxes = Vec::new();
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_box(&line) {
+            Ok(b) => boxes.push(b),
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+    boxes
+}
+
+// Pulls each coordinate onto the closest box edge within the threshold. Only edges whose
+// extent (grown by the threshold) spans the point on the other axis are considered.
+pub fn snap_to_edges(pos: (f64, f64), boxes: &[CandidateBox], threshold: f64) -> (f64, f64) {
+    let mut best_x: Option<(f64, f64)> = None;
+    let mut best_y: Option<(f64, f64)> = None;
+    let consider = |best: &mut Option<(f64, f64)>, value: f64, edge: f64| {
+        let distance = (value - edge).abs();
+        if distance <= threshold && best.is_none_or(|(d, _)| distance < d) {
+            *best = Some((distance, edge));
+        }
+    };
+
+    for b in boxes {
+        let (x, y, w, h) = b.rect;
+        if pos.1 >= y - threshold && pos.1 <= y + h + threshold {
+            consider(&mut best_x, pos.0, x);
+            consider(&mut best_x, pos.0, x + w);
+        }
+        if pos.0 >= x - threshold && pos.0 <= x + w + threshold {
+            consider(&mut best_y, pos.1, y);
+            consider(&mut best_y, pos.1, y + h);
+        }
+    }
+
+    (best_x.map_or(pos.0, |(_, edge)| edge), best_y.map_or(pos.1, |(_, edge)| edge))
+}
@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use pango::FontDescription;
+
+use crate::color::Color;
+use crate::options::{ButtonAction, Options, OutputTheme, RenderBackend};
+use crate::Rect;
+
+// Programmatic counterpart of the command line flags, every setter maps onto one Options field
+// so embedding code keeps compiling as flags are added
+#[derive(Debug, Clone, Default)]
+pub struct SelectionOptions {
+    options: Options,
+}
+
+impl SelectionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn no_dim(mut self, no_dim: bool) -> Self {
+        self.options.no_dim = no_dim;
+        self
+    }
+
+    pub fn background_color(mut self, color: Color) -> Self {
+        self.options.background_color = color;
+        self
+    }
+
+    // Same as --dim-opacity, 0 disables dimming
+    pub fn dim_opacity(mut self, opacity: f64) -> Self {
+        self.options.background_color.a = opacity.clamp(0.0, 1.0);
+        self.options.no_dim = opacity <= 0.0;
+        self
+    }
+
+    pub fn border_color(mut self, color: Color) -> Self {
+        self.options.border_color = color;
+        self
+    }
+
+    pub fn selection_color(mut self, color: Color) -> Self {
+        self.options.selection_color = color;
+        self
+    }
+
+    pub fn box_color(mut self, color: Color) -> Self {
+        self.options.box_color = color;
+        self
+    }
+
+    // Only these outputs get an overlay, all of them when empty
+    pub fn outputs<S: Into<String>>(mut self, outputs: impl IntoIterator<Item = S>) -> Self {
+        self.options.outputs = outputs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // Later themes for the same output override the settings of earlier ones
+    pub fn output_theme(mut self, output: impl Into<String>, theme: OutputTheme) -> Self {
+        self.options.output_themes.push((output.into(), theme));
+        self
+    }
+
+    pub fn border_weight(mut self, weight: u32) -> Self {
+        self.options.border_weight = weight as f64;
+        self
+    }
+
+    pub fn dashed_border(mut self, pattern: &[f64]) -> Self {
+        self.options.border_dash = Some(pattern.to_vec());
+        self
+    }
+
+    pub fn corner_radius(mut self, radius: u32) -> Self {
+        self.options.corner_radius = radius as f64;
+        self
+    }
+
+    pub fn font(mut self, font: &str) -> Self {
+        self.options.font = FontDescription::from_string(font);
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Self {
+        self.options.format = Some(format.to_string());
+        self
+    }
+
+    pub fn float(mut self, float: bool) -> Self {
+        self.options.float = float;
+        self
+    }
+
+    pub fn pixels(mut self, pixels: bool) -> Self {
+        self.options.pixels = pixels;
+        self
+    }
+
+    pub fn single_output(mut self, single_output: bool) -> Self {
+        self.options.single_output = single_output;
+        self
+    }
+
+    // Replaces the button's current binding, buttons are evdev codes like BTN_LEFT (0x110)
+    pub fn bind_button(mut self, button: u32, action: ButtonAction) -> Self {
+        self.options.button_bindings.retain(|&(b, _)| b != button);
+        self.options.button_bindings.push((button, action));
+        self
+    }
+
+    // A socket name in XDG_RUNTIME_DIR or a path, used instead of WAYLAND_DISPLAY
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.options.display = Some(display.into());
+        self
+    }
+
+    pub fn point(mut self, point: bool) -> Self {
+        self.options.point = point;
+        self
+    }
+
+    pub fn output(mut self, output: bool) -> Self {
+        self.options.output = output;
+        self
+    }
+
+    pub fn restrict(mut self, restrict: bool) -> Self {
+        self.options.restrict = restrict;
+        self
+    }
+
+    pub fn aspect_ratio(mut self, width: u32, height: u32) -> Self {
+        self.options.aspect_ratio = (width > 0 && height > 0).then(|| width as f64 / height as f64);
+        self
+    }
+
+    pub fn fixed_size(mut self, width: u32, height: u32) -> Self {
+        self.options.fixed_size = (width > 0 && height > 0).then_some((width as f64, height as f64));
+        self
+    }
+
+    // Smaller drags are rejected instead of selected or cancelled
+    pub fn min_size(mut self, width: u32, height: u32) -> Self {
+        self.options.min_size = Some((width as f64, height as f64));
+        self
+    }
+
+    pub fn step(mut self, step: u32) -> Self {
+        self.options.step = step as f64;
+        self
+    }
+
+    pub fn scroll_step(mut self, scroll_step: u32) -> Self {
+        self.options.scroll_step = scroll_step as f64;
+        self
+    }
+
+    pub fn multiple(mut self, multiple: bool) -> Self {
+        self.options.multiple = multiple;
+        self
+    }
+
+    pub fn edit(mut self, edit: bool) -> Self {
+        self.options.edit = edit;
+        self
+    }
+
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.options.confirm = confirm;
+        self
+    }
+
+    // Like --initial, also turns on edit mode
+    pub fn initial(mut self, rect: Rect) -> Self {
+        self.options.initial = Some(rect);
+        self.options.edit = true;
+        self
+    }
+
+    pub fn snap(mut self, grid: u32) -> Self {
+        self.options.snap = (grid > 0).then_some(grid as f64);
+        self
+    }
+
+    pub fn show_grid(mut self, show_grid: bool) -> Self {
+        self.options.show_grid = show_grid;
+        self
+    }
+
+    pub fn snap_edges(mut self, threshold: f64) -> Self {
+        self.options.snap_edges = (threshold.is_finite() && threshold >= 0.0).then_some(threshold);
+        self
+    }
+
+    pub fn windows(mut self, windows: bool) -> Self {
+        self.options.windows = windows;
+        self
+    }
+
+    pub fn freeze(mut self, freeze: bool) -> Self {
+        self.options.freeze = freeze;
+        self
+    }
+
+    pub fn magnifier(mut self, zoom: u32) -> Self {
+        self.options.magnifier = (zoom >= 2).then_some(zoom as f64);
+        self
+    }
+
+    pub fn show_dimensions(mut self, show_dimensions: bool) -> Self {
+        self.options.show_dimensions = show_dimensions;
+        self
+    }
+
+    pub fn show_position(mut self, show_position: bool) -> Self {
+        self.options.show_position = show_position;
+        self
+    }
+
+    pub fn guides(mut self, guides: bool) -> Self {
+        self.options.guides = guides;
+        self
+    }
+
+    pub fn hide_cursor(mut self, hide_cursor: bool) -> Self {
+        self.options.hide_cursor = hide_cursor;
+        self
+    }
+
+    // Has no effect with the xdg-shell fallback, and layer-shell below version 4 always grabs the keyboard
+    pub fn grab_keyboard(mut self, grab_keyboard: bool) -> Self {
+        self.options.grab_keyboard = grab_keyboard;
+        self
+    }
+
+    pub fn inhibit_shortcuts(mut self, inhibit_shortcuts: bool) -> Self {
+        self.options.inhibit_shortcuts = inhibit_shortcuts;
+        self
+    }
+
+    // Saves the selected region as PNG, taken from the screen as it was when selecting started
+    pub fn capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.capture = Some(path.into());
+        self
+    }
+
+    // RenderBackend::Gpu needs the gpu feature and falls back to the CPU without it
+    pub fn renderer(mut self, renderer: RenderBackend) -> Self {
+        self.options.renderer = renderer;
+        self
+    }
+
+    // Fails with Error::Timeout if nothing was selected in time
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    // SIGINT and SIGTERM cancel, SIGUSR1 confirms, replacing the process's handlers while selecting
+    pub fn handle_signals(mut self, handle_signals: bool) -> Self {
+        self.options.handle_signals = handle_signals;
+        self
+    }
+
+    pub fn build(self) -> Options {
+        self.options
+    }
+}
+
+impl From<SelectionOptions> for Options {
+    fn from(builder: SelectionOptions) -> Self {
+        builder.build()
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{Rect, Selection};
+
+// Closures invoked from the event loop, so embedding applications can follow the selection live
+#[derive(Default)]
+pub struct Callbacks {
+    pub(crate) pointer_move: Option<Box<dyn FnMut((f64, f64))>>,
+    pub(crate) selection_changed: Option<Box<dyn FnMut(Option<Rect>)>>,
+    pub(crate) selection_changed_with_output: Option<Box<dyn FnMut(Option<&Selection>)>>,
+    pub(crate) confirmed: Option<Box<dyn FnMut(&[Selection])>>,
+    pub(crate) cancelled: Option<Box<dyn FnMut()>>,
+}
+
+impl Callbacks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Called with the global cursor position whenever it moves
+    pub fn on_pointer_move(mut self, f: impl FnMut((f64, f64)) + 'static) -> Self {
+        self.pointer_move = Some(Box::new(f));
+        self
+    }
+
+    // Called with the region on screen whenever it changes, None when there is none
+    pub fn on_selection_changed(mut self, f: impl FnMut(Option<Rect>) + 'static) -> Self {
+        self.selection_changed = Some(Box::new(f));
+        self
+    }
+
+    // Like on_selection_changed, along with the output the region would be reported on
+    pub fn on_selection_changed_with_output(mut self, f: impl FnMut(Option<&Selection>) + 'static) -> Self {
+        self.selection_changed_with_output = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_confirmed(mut self, f: impl FnMut(&[Selection]) + 'static) -> Self {
+        self.confirmed = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_cancelled(mut self, f: impl FnMut() + 'static) -> Self {
+        self.cancelled = Some(Box::new(f));
+        self
+    }
+}
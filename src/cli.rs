@@ -0,0 +1,521 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use pango::FontDescription;
+
+use crate::boxes::{parse_rect, parse_size};
+use crate::format;
+use crate::color::Color;
+pub use crate::options::{ButtonAction, Options, OutputTheme, RenderBackend};
+use crate::options::{BTN_LEFT, BTN_MIDDLE, BTN_RIGHT};
+
+const DEFAULT_DASH_PATTERN: [f64; 2] = [6.0, 4.0];
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum BorderStyle {
+    Solid,
+    Dashed,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a completion script for the given shell to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "rust-slurp",
+    version,
+    about = "Select a region in a Wayland compositor and print it to stdout",
+    after_help = "Options showing an [env: SLURP_*] variable take their default from it, flags on the command line take precedence.
+
+Exit status:
+  0  a selection was made
+  1  the selection was cancelled
+  2  the compositor lacks a required Wayland protocol
+  3  an error occurred, such as invalid arguments or a lost compositor connection
+  4  no selection was made before --timeout",
+    args_conflicts_with_subcommands = true,
+    args_override_self = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Don't dim the screen outside of the selection
+    #[arg(short = 'd', long = "no-dim")]
+    no_dim: bool,
+
+    /// Set background color (#RRGGBB[AA])
+    #[arg(short = 'b', env = "SLURP_BACKGROUND_COLOR", value_name = "COLOR")]
+    background: Option<Color>,
+
+    /// Set the opacity of the dimmed background (0..1), 0 disables dimming
+    #[arg(long = "dim-opacity", env = "SLURP_DIM_OPACITY", value_name = "OPACITY", value_parser = parse_opacity)]
+    dim_opacity: Option<f64>,
+
+    /// Set border color (#RRGGBB[AA])
+    #[arg(short = 'c', env = "SLURP_BORDER_COLOR", value_name = "COLOR")]
+    border: Option<Color>,
+
+    /// Set selection color (#RRGGBB[AA])
+    #[arg(short = 's', env = "SLURP_SELECTION_COLOR", value_name = "COLOR")]
+    selection: Option<Color>,
+
+    /// Set color of the predefined boxes (#RRGGBB[AA])
+    #[arg(short = 'B', env = "SLURP_BOX_COLOR", value_name = "COLOR")]
+    box_color: Option<Color>,
+
+    /// Set border weight in pixels, 0 disables the border
+    #[arg(short = 'w', env = "SLURP_BORDER_WEIGHT", value_name = "PIXELS")]
+    border_weight: Option<u32>,
+
+    /// Set output format (%g %x %y %w %h %X %Y %W %H %o %s %l %a %t)
+    ///
+    /// Defaults to "%x,%y %wx%h", followed by " %l" when the chosen box has a label.
+    /// %a and %t are the app_id and title of the chosen window with --windows, %s is the output's scale.
+    #[arg(short = 'f', env = "SLURP_FORMAT", value_name = "FORMAT")]
+    format: Option<String>,
+
+    /// Follow the default format with the name of the output holding most of the selection
+    #[arg(long = "print-output", conflicts_with_all = ["format", "recorder"])]
+    print_output: bool,
+
+    /// Select a single point instead of a region
+    #[arg(short = 'p')]
+    point: bool,
+
+    /// Select a whole output by clicking on it
+    #[arg(short = 'o')]
+    output: bool,
+
+    /// Restrict selection to predefined boxes read from stdin
+    #[arg(short = 'r')]
+    restrict: bool,
+
+    /// Force the selection to an aspect ratio (e.g. 16:9)
+    #[arg(short = 'a', value_name = "W:H", value_parser = parse_aspect_ratio)]
+    aspect_ratio: Option<f64>,
+
+    /// Select a region of a fixed size (e.g. 1280x720) that follows the cursor
+    #[arg(long = "size", value_name = "WxH", value_parser = parse_size)]
+    fixed_size: Option<(f64, f64)>,
+
+    /// Distance in pixels the arrow keys move the cursor, or resize the selection with Shift
+    #[arg(long = "step", env = "SLURP_STEP", value_name = "PIXELS", default_value_t = 10)]
+    step: u32,
+
+    /// Select several regions, confirm them all with Enter
+    #[arg(short = 'm', long = "multiple")]
+    multiple: bool,
+
+    /// Keep the selection on screen with resize handles until it is confirmed with Enter
+    #[arg(long = "edit")]
+    edit: bool,
+
+    /// Start with this region already selected and editable (implies --edit)
+    #[arg(long = "initial", value_name = "x,y WxH", value_parser = parse_rect)]
+    initial: Option<(f64, f64, f64, f64)>,
+
+    /// Snap selection corners to a grid of this many pixels
+    #[arg(long = "snap", value_name = "PIXELS", value_parser = parse_grid)]
+    snap: Option<f64>,
+
+    /// Render the snapping grid on the overlay
+    #[arg(long = "show-grid", requires = "snap")]
+    show_grid: bool,
+
+    /// Snap selection edges to the edges of boxes read from stdin within this many pixels
+    #[arg(long = "snap-edges", value_name = "PIXELS", value_parser = parse_snap_distance)]
+    snap_edges: Option<f64>,
+
+    /// Offer the visible windows of the running compositor as selectable boxes
+    #[arg(long = "windows")]
+    windows: bool,
+
+    /// Freeze the screen while selecting, using a screenshot as the overlay background
+    #[arg(long = "freeze")]
+    freeze: bool,
+
+    /// Show a zoomed view of the pixels around the cursor, optionally with the zoom factor
+    #[arg(long = "magnifier", value_name = "ZOOM", num_args = 0..=1, default_missing_value = "8", value_parser = parse_zoom)]
+    magnifier: Option<f64>,
+
+    /// Show the size of the selection next to the cursor while dragging
+    #[arg(long = "show-dimensions")]
+    show_dimensions: bool,
+
+    /// Show the global position of the cursor in a tooltip next to it
+    #[arg(long = "show-position")]
+    show_position: bool,
+
+    /// Set the font of text on the overlay as a pango font description (e.g. "monospace 11")
+    #[arg(long = "font", env = "SLURP_FONT", value_name = "FONT")]
+    font: Option<String>,
+
+    /// Extend the crosshair into guide lines across all outputs
+    #[arg(long = "guides")]
+    guides: bool,
+
+    /// Set the style of the selection border
+    #[arg(long = "border-style", env = "SLURP_BORDER_STYLE", value_name = "STYLE", value_enum, default_value_t = BorderStyle::Solid)]
+    border_style: BorderStyle,
+
+    /// Set the dash pattern of a dashed border as comma separated lengths in pixels
+    #[arg(long = "dash-pattern", env = "SLURP_DASH_PATTERN", value_name = "ON,OFF", value_delimiter = ',', value_parser = parse_dash_length)]
+    dash_pattern: Option<Vec<f64>>,
+
+    /// Round the corners of the selection with this radius in pixels
+    #[arg(long = "corner-radius", env = "SLURP_CORNER_RADIUS", value_name = "PIXELS")]
+    corner_radius: Option<u32>,
+
+    /// Cancel if no selection was made within this many milliseconds
+    #[arg(long = "timeout", value_name = "MS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+
+    /// Keep the selection on screen with Accept/Cancel buttons after releasing the mouse button
+    #[arg(long = "confirm")]
+    confirm: bool,
+
+    /// Print the selection while it changes, followed by a line reading "done" once it is confirmed
+    #[arg(long = "live")]
+    live: bool,
+
+    /// Also copy the result to the clipboard (needs wlr-data-control)
+    #[arg(long = "copy")]
+    copy: bool,
+
+    /// Write the result to this file instead of stdout, replacing it atomically ("-" is stdout)
+    #[arg(long = "output-file", env = "SLURP_OUTPUT_FILE", value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// Terminate each result with a NUL byte instead of a newline
+    #[arg(long = "print0", conflicts_with = "no_newline")]
+    print0: bool,
+
+    /// Leave out the newline after the last result
+    #[arg(long = "no-newline")]
+    no_newline: bool,
+
+    /// Run a shell command after selecting, with the same placeholders as -f, and exit with its status
+    ///
+    /// Placeholders are passed as shell variables such as ${SLURP_X}, so labels and titles are never run as
+    /// shell code. They expand unquoted and inside single or double quotes.
+    #[arg(long = "exec", value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// Save the selected region as a PNG, as the screen looked when the selection started
+    #[arg(long = "capture", value_name = "FILE")]
+    capture: Option<PathBuf>,
+
+    /// Print output-local geometry, the output name and its scale when the selection is on a single output
+    #[arg(long = "recorder", conflicts_with = "format")]
+    recorder: bool,
+
+    /// Run as a D-Bus service (org.rustslurp.Selector) on the session bus instead of selecting once
+    #[arg(long = "dbus")]
+    dbus: bool,
+
+    /// Keep running and make a selection for every request on the daemon socket
+    ///
+    /// A request is a line "select", optionally followed by command line flags for that selection,
+    /// separated by NUL bytes so their values can contain spaces. Flags of the request override the
+    /// daemon's own. The reply is "ok" followed by the results, "cancelled" or "error MESSAGE".
+    #[arg(long = "daemon", conflicts_with = "dbus")]
+    daemon: bool,
+
+    /// Socket of --daemon, defaults to $XDG_RUNTIME_DIR/rust-slurp.sock
+    #[arg(long = "socket", env = "SLURP_SOCKET", value_name = "PATH", requires = "daemon")]
+    socket: Option<PathBuf>,
+
+    /// Take JSON-RPC commands on stdin and report events as JSON on stdout
+    ///
+    /// Methods: set_selection {x, y, width, height}, set_colors {background, border, selection, box},
+    /// confirm and cancel. Events: pointer_moved, selection_changed, confirmed and cancelled.
+    #[arg(long = "control", conflicts_with_all = ["live", "daemon", "dbus", "restrict", "snap_edges"])]
+    control: bool,
+
+    /// Draw the overlay with the CPU or with OpenGL ES (needs the gpu feature)
+    ///
+    /// With gpu the dim layer, the frozen frame and the selection fills are drawn by OpenGL ES into EGL window
+    /// surfaces, whose buffers the EGL driver allocates. Borders and labels are still drawn by cairo and uploaded
+    /// where they changed. Without a usable EGL display the CPU renderer is used.
+    #[arg(long = "renderer", env = "SLURP_RENDERER", value_name = "RENDERER", value_enum, default_value_t = RenderBackend::Cpu)]
+    renderer: RenderBackend,
+
+    /// Log to stderr, -v for debug and -vv for trace messages (RUST_LOG takes precedence)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Hide the pointer cursor over the overlay, leaving the drawn crosshair as the only indicator
+    #[arg(long = "hide-cursor")]
+    hide_cursor: bool,
+
+    /// Take all keyboard input while selecting so compositor keybindings can't steal keys
+    ///
+    /// Compositors with layer-shell older than version 4 only offer exclusive keyboard input, so there it's
+    /// always taken.
+    #[arg(long = "grab-keyboard")]
+    grab_keyboard: bool,
+
+    /// Keep compositor shortcuts from firing while the overlay has keyboard focus
+    #[arg(long = "inhibit-shortcuts")]
+    inhibit_shortcuts: bool,
+
+    /// Reject drags smaller than this (e.g. 50x50), they are outlined in red and releasing them keeps the selector open
+    ///
+    /// Drags collapsed to a line are rejected the same way without it. Resize handles, the scroll wheel and
+    /// typed regions can't go below the minimum either.
+    #[arg(long = "min-size", value_name = "WxH", value_parser = parse_size, conflicts_with_all = ["point", "fixed_size"])]
+    min_size: Option<(f64, f64)>,
+
+    /// Print unrounded coordinates, by default the edges are rounded to whole pixels
+    #[arg(long = "float")]
+    float: bool,
+
+    /// Print the selection in buffer pixels of the output holding most of it, relative to that output
+    #[arg(long = "pixels", conflicts_with_all = ["recorder", "live"])]
+    pixels: bool,
+
+    /// Keep the selection on the output it was started on
+    #[arg(long = "single-output")]
+    single_output: bool,
+
+    /// Bind a mouse or stylus button to an action, e.g. "right=start-selection" (repeatable)
+    ///
+    /// Buttons are left, right, middle, side, extra, forward, back, stylus, stylus2 or an evdev code such as 0x110.
+    /// Actions are start-selection, cancel, confirm, pick-box and pick (the box under the cursor or
+    /// else the output). Left starts a selection, middle picks and right cancels unless rebound.
+    #[arg(long = "bind", env = "SLURP_BIND", value_name = "BUTTON=ACTION", value_delimiter = ',', value_parser = parse_binding)]
+    bind: Vec<(u32, ButtonAction)>,
+
+    /// Pixels the scroll wheel grows or shrinks the selection by per notch, Shift limits it to the width and Alt to the height
+    #[arg(long = "scroll-step", env = "SLURP_SCROLL_STEP", value_name = "PIXELS", default_value_t = 10)]
+    scroll_step: u32,
+
+    /// Override colors and dimming on one output, e.g. "DP-1:dim-opacity=0.2,border=#ffffff"
+    ///
+    /// Keys are background, dim-opacity, border, selection and box. Repeat for more outputs.
+    #[arg(long = "output-theme", value_name = "NAME:KEY=VALUE,...", value_parser = parse_output_theme)]
+    output_themes: Vec<(String, OutputTheme)>,
+
+    /// Connect to this Wayland display instead of $WAYLAND_DISPLAY, a socket name in $XDG_RUNTIME_DIR or a path
+    #[arg(long = "display", value_name = "NAME")]
+    display: Option<String>,
+
+    /// Only show the overlay on these outputs, e.g. "DP-1,HDMI-A-1"
+    #[arg(long = "outputs", value_name = "NAME,...", value_delimiter = ',')]
+    outputs: Vec<String>,
+}
+
+impl From<Cli> for Options {
+    fn from(cli: Cli) -> Self {
+        let defaults = Options::default();
+        let mut background_color = cli.background.unwrap_or(defaults.background_color);
+        if let Some(opacity) = cli.dim_opacity {
+            background_color.a = opacity;
+        }
+        Options {
+            no_dim: cli.no_dim || cli.dim_opacity == Some(0.0),
+            background_color,
+            border_color: cli.border.unwrap_or(defaults.border_color),
+            selection_color: cli.selection.unwrap_or(defaults.selection_color),
+            box_color: cli.box_color.unwrap_or(defaults.box_color),
+            border_weight: cli.border_weight.map_or(defaults.border_weight, |w| w as f64),
+            format: cli.format.or_else(|| cli.print_output.then(|| format::OUTPUT_FORMAT.to_string())),
+            point: cli.point,
+            output: cli.output,
+            restrict: cli.restrict,
+            aspect_ratio: cli.aspect_ratio,
+            fixed_size: cli.fixed_size,
+            step: cli.step as f64,
+            multiple: cli.multiple,
+            edit: cli.edit || cli.initial.is_some(),
+            initial: cli.initial,
+            snap: cli.snap,
+            show_grid: cli.show_grid,
+            snap_edges: cli.snap_edges,
+            windows: cli.windows,
+            freeze: cli.freeze,
+            magnifier: cli.magnifier,
+            show_dimensions: cli.show_dimensions,
+            show_position: cli.show_position,
+            font: cli.font.map_or(defaults.font, |font| FontDescription::from_string(&font)),
+            guides: cli.guides,
+            border_dash: (cli.border_style == BorderStyle::Dashed).then(|| cli.dash_pattern.unwrap_or_else(|| DEFAULT_DASH_PATTERN.to_vec())),
+            corner_radius: cli.corner_radius.map_or(defaults.corner_radius, |r| r as f64),
+            // Only the binary owns the process, library callers may have their own handlers
+            handle_signals: true,
+            timeout: cli.timeout,
+            confirm: cli.confirm,
+            live: cli.live,
+            copy: cli.copy,
+            output_file: cli.output_file.filter(|path| path.as_os_str() != "-"),
+            print0: cli.print0,
+            no_newline: cli.no_newline,
+            exec: cli.exec,
+            capture: cli.capture,
+            recorder: cli.recorder,
+            dbus: cli.dbus,
+            daemon: cli.daemon,
+            socket: cli.socket,
+            control: cli.control,
+            renderer: cli.renderer,
+            verbose: cli.verbose,
+            hide_cursor: cli.hide_cursor,
+            grab_keyboard: cli.grab_keyboard,
+            inhibit_shortcuts: cli.inhibit_shortcuts,
+            min_size: cli.min_size,
+            float: cli.float,
+            pixels: cli.pixels,
+            single_output: cli.single_output,
+            button_bindings: merge_bindings(defaults.button_bindings, cli.bind),
+            scroll_step: cli.scroll_step as f64,
+            output_themes: cli.output_themes,
+            display: cli.display,
+            outputs: cli.outputs,
+        }
+    }
+}
+
+pub(crate) fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid aspect ratio '{}', expected W:H", s);
+    let (w, h) = s.split_once(':').ok_or_else(invalid)?;
+    let w: f64 = w.trim().parse().map_err(|_| invalid())?;
+    let h: f64 = h.trim().parse().map_err(|_| invalid())?;
+    if !(w > 0.0 && h > 0.0 && w.is_finite() && h.is_finite()) {
+        return Err(invalid());
+    }
+    Ok(w / h)
+}
+
+fn parse_opacity(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(format!("invalid opacity '{}', expected a number between 0 and 1", s)),
+    }
+}
+
+fn parse_zoom(s: &str) -> Result<f64, String> {
+    match s.parse::<u32>() {
+        Ok(n) if n >= 2 => Ok(n as f64),
+        _ => Err(format!("invalid zoom '{}', expected a whole number of at least 2", s)),
+    }
+}
+
+fn parse_dash_length(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("invalid dash length '{}', expected a positive number of pixels", s)),
+    }
+}
+
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    match s.parse::<u64>() {
+        Ok(ms) if ms > 0 => Ok(Duration::from_millis(ms)),
+        _ => Err(format!("invalid timeout '{}', expected a positive number of milliseconds", s)),
+    }
+}
+
+fn parse_output_theme(s: &str) -> Result<(String, OutputTheme), String> {
+    let (name, settings) = s.split_once(':').ok_or_else(|| format!("invalid output theme '{}', expected NAME:KEY=VALUE,...", s))?;
+    let mut theme = OutputTheme::default();
+    for setting in settings.split(',') {
+        let (key, value) = setting.split_once('=').ok_or_else(|| format!("invalid theme setting '{}', expected KEY=VALUE", setting))?;
+        let value = value.trim();
+        match key.trim() {
+            "background" => theme.background_color = Some(value.parse()?),
+            "dim-opacity" => theme.dim_opacity = Some(parse_opacity(value)?),
+            "border" => theme.border_color = Some(value.parse()?),
+            "selection" => theme.selection_color = Some(value.parse()?),
+            "box" => theme.box_color = Some(value.parse()?),
+            key => return Err(format!("unknown theme setting '{}', expected background, dim-opacity, border, selection or box", key)),
+        }
+    }
+    Ok((name.trim().to_string(), theme))
+}
+
+fn parse_binding(s: &str) -> Result<(u32, ButtonAction), String> {
+    let invalid = || format!("invalid binding '{}', expected BUTTON=ACTION", s);
+    let (button, action) = s.split_once('=').ok_or_else(invalid)?;
+    let button = match button.trim() {
+        "left" => BTN_LEFT,
+        "right" => BTN_RIGHT,
+        "middle" => BTN_MIDDLE,
+        "side" => 0x113,
+        "extra" => 0x114,
+        "forward" => 0x115,
+        "back" => 0x116,
+        "stylus" => 0x14b,
+        "stylus2" => 0x14c,
+        code => match code.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => code.parse(),
+        }
+        .map_err(|_| format!("invalid button '{}'", code))?,
+    };
+    let action = ButtonAction::from_str(action.trim(), true).map_err(|_| format!("invalid action '{}'", action.trim()))?;
+    Ok((button, action))
+}
+
+// A binding replaces the default one of its button
+fn merge_bindings(mut bindings: Vec<(u32, ButtonAction)>, overrides: Vec<(u32, ButtonAction)>) -> Vec<(u32, ButtonAction)> {
+    for (button, action) in overrides {
+        bindings.retain(|&(b, _)| b != button);
+        bindings.push((button, action));
+    }
+    bindings
+}
+
+fn parse_grid(s: &str) -> Result<f64, String> {
+    match s.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n as f64),
+        _ => Err(format!("invalid grid size '{}', expected a positive number of pixels", s)),
+    }
+}
+
+fn parse_snap_distance(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+        _ => Err(format!("invalid snapping distance '{}', expected a non-negative number of pixels", s)),
+    }
+}
+
+pub fn parse() -> Options {
+    match Cli::try_parse() {
+        Ok(Cli { command: Some(Command::Completions { shell }), .. }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "rust-slurp", &mut io::stdout());
+            exit(crate::EXIT_SUCCESS);
+        }
+        Ok(cli) => cli.into(),
+        // Clap exits with 2 on usage errors, which is reserved for missing protocols
+        Err(e) => {
+            let code = if e.use_stderr() { crate::EXIT_ERROR } else { crate::EXIT_SUCCESS };
+            e.print().unwrap();
+            exit(code);
+        }
+    }
+}
+
+// Parses flags given without the program name, e.g. a request sent to the daemon
+pub fn try_parse_from<I, T>(args: I) -> Result<Options, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = std::iter::once(OsString::from("slurp")).chain(args.into_iter().map(Into::into));
+    match Cli::try_parse_from(args)? {
+        Cli { command: Some(_), .. } => Err(Cli::command().error(ErrorKind::InvalidSubcommand, "subcommands can't be used in a selection request")),
+        cli => Ok(cli.into()),
+    }
+}
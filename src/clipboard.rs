@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+
+use wayland_client::protocol::{wl_registry, wl_seat::{self, WlSeat}};
+use wayland_client::{event_created_child, Connection, Dispatch, QueueHandle};
+
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::{self, ZwlrDataControlManagerV1},
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::error::Error;
+
+const MIME_TYPES: [&str; 5] = ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING", "TEXT", "STRING"];
+
+// Owns the clipboard through wlr-data-control, which works without a focused surface
+struct Clipboard {
+    text: String,
+    manager: Option<ZwlrDataControlManagerV1>,
+    seat: Option<WlSeat>,
+    // Cleared once another client takes over the clipboard
+    serving: bool,
+}
+
+// Puts the text on the clipboard and keeps serving it from a forked child, like wl-copy does,
+// so the caller can exit right away
+pub fn copy_in_background(text: String, display: Option<&str>) -> Result<(), Error> {
+    let conn = crate::connect(display)?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+    conn.display().get_registry(&qh, ());
+
+    let mut clipboard = Clipboard { text, manager: None, seat: None, serving: true };
+    event_queue.roundtrip(&mut clipboard)?;
+
+    let (Some(manager), Some(seat)) = (clipboard.manager.as_ref(), clipboard.seat.as_ref()) else {
+        return Err(Error::MissingProtocols("zwlr_data_control_manager_v1".to_string()));
+    };
+    let source = manager.create_data_source(&qh, ());
+    for mime_type in MIME_TYPES {
+        source.offer(mime_type.to_string());
+    }
+    let device = manager.get_data_device(seat, &qh, ());
+    device.set_selection(Some(&source));
+    event_queue.roundtrip(&mut clipboard)?;
+
+    match unsafe { libc::fork() } {
+        -1 => Err(Error::Fork(io::Error::last_os_error())),
+        0 => {
+            // The child must not keep stdout open, `$(slurp --copy)` would wait for it otherwise
+            if let Ok(null) = File::options().write(true).open("/dev/null") {
+                unsafe { libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO) };
+            }
+            while clipboard.serving {
+                if event_queue.blocking_dispatch(&mut clipboard).is_err() {
+                    break;
+                }
+            }
+            source.destroy();
+            device.destroy();
+            std::process::exit(0);
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for Clipboard {
+    fn event(state: &mut Self, registry: &wl_registry::WlRegistry, event: wl_registry::Event, _: &(), _: &Connection, qh: &QueueHandle<Self>) {
+        if let wl_registry::Event::Global { name, interface, version } = event {
+            match interface.as_str() {
+                "zwlr_data_control_manager_v1" => {
+                    state.manager = Some(registry.bind(name, version.min(2), qh, ()));
+                }
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, version.min(7), qh, ()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, ()> for Clipboard {
+    fn event(state: &mut Self, _: &ZwlrDataControlSourceV1, event: zwlr_data_control_source_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            // A paste that fails half way only affects the pasting client
+            zwlr_data_control_source_v1::Event::Send { fd, .. } => {
+                let _ = File::from(fd).write_all(state.text.as_bytes());
+            }
+            zwlr_data_control_source_v1::Event::Cancelled => state.serving = false,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for Clipboard {
+    fn event(state: &mut Self, _: &ZwlrDataControlDeviceV1, event: zwlr_data_control_device_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            // Offers for the current selection aren't needed, only our own source matters
+            zwlr_data_control_device_v1::Event::Selection { id: Some(offer) } | zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+            zwlr_data_control_device_v1::Event::Finished => state.serving = false,
+            _ => {}
+        }
+    }
+
+    event_created_child!(Clipboard, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for Clipboard { fn event(_: &mut Self, _: &ZwlrDataControlManagerV1, _: zwlr_data_control_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<ZwlrDataControlOfferV1, ()> for Clipboard { fn event(_: &mut Self, _: &ZwlrDataControlOfferV1, _: zwlr_data_control_offer_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<WlSeat, ()> for Clipboard { fn event(_: &mut Self, _: &WlSeat, _: wl_seat::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
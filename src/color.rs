@@ -0,0 +1,31 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    pub const fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    // Accepts #RRGGBB or #RRGGBBAA, the leading '#' is optional like in slurp
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid color '{}', expected #RRGGBB or #RRGGBBAA", s));
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f64 / 255.0;
+        let a = if hex.len() == 8 { channel(6) } else { 1.0 };
+        Ok(Color::new(channel(0), channel(2), channel(4), a))
+    }
+}
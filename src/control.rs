@@ -0,0 +1,159 @@
+use std::io::{self, BufRead, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use crate::color::Color;
+use crate::error::Error;
+use crate::{event_loop, setup, signals, Callbacks, CandidateBox, Options, Rect, Selection, State};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Lines from stdin, read on a thread so the event loop only has to poll the wake socket
+struct Commands {
+    receiver: Receiver<String>,
+    wake: UnixStream,
+}
+
+impl Commands {
+    fn spawn() -> io::Result<Self> {
+        let (wake, wake_writer) = UnixStream::pair()?;
+        wake.set_nonblocking(true)?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+                let _ = (&wake_writer).write_all(&[0]);
+            }
+            // The receiver sees the channel disconnect once the sender is gone
+            drop(sender);
+            let _ = (&wake_writer).write_all(&[0]);
+        });
+        Ok(Commands { receiver, wake })
+    }
+
+    // Closing stdin cancels, the wrapper that would receive the result is gone
+    fn handle(&self, state: &mut State) {
+        let mut buf = [0u8; 64];
+        while matches!((&self.wake).read(&mut buf), Ok(n) if n > 0) {}
+        loop {
+            match self.receiver.try_recv() {
+                Ok(line) => {
+                    if let Some(response) = execute(state, &line) {
+                        println!("{}", response);
+                    }
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    state.running = false;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Runs a selection driven by JSON-RPC 2.0 requests on stdin, reporting events as notifications on stdout
+pub fn select_with_control(options: Options, boxes: Vec<CandidateBox>) -> Result<Vec<Selection>, Error> {
+    let commands = Commands::spawn().map_err(Error::Control)?;
+    let (_conn, mut event_queue, mut state) = setup(options, boxes, event_callbacks(), true)?;
+    let signals = if state.options.handle_signals { Some(signals::Signals::register()?) } else { None };
+    let deadline = state.options.timeout.map(|timeout| Instant::now() + timeout);
+    while state.running {
+        event_loop::dispatch(&mut event_queue, &mut state, signals.as_ref(), deadline, &[commands.wake.as_raw_fd()])?;
+        commands.handle(&mut state);
+    }
+    state.take_results()
+}
+
+fn notify(method: &str, params: Value) {
+    println!("{}", json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+}
+
+fn rect_json((x, y, width, height): Rect) -> Value {
+    json!({ "x": x, "y": y, "width": width, "height": height })
+}
+
+fn event_callbacks() -> Callbacks {
+    Callbacks::new()
+        .on_pointer_move(|(x, y)| notify("pointer_moved", json!({ "x": x, "y": y })))
+        .on_selection_changed(|rect| notify("selection_changed", rect.map_or(Value::Null, rect_json)))
+        .on_confirmed(|selections| {
+            let selections: Vec<Value> = selections
+                .iter()
+                .map(|s| {
+                    let mut selection = rect_json(s.rect);
+                    selection["output"] = s.output_name.as_deref().map_or(Value::Null, Value::from);
+                    selection
+                })
+                .collect();
+            notify("confirmed", json!({ "selections": selections }));
+        })
+        .on_cancelled(|| notify("cancelled", Value::Null))
+}
+
+// Requests without an id are notifications and get no response
+fn execute(state: &mut State, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": e.to_string() } })),
+    };
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => call(state, method, request.get("params").unwrap_or(&Value::Null)),
+        None => Err((INVALID_REQUEST, "missing method".to_string())),
+    };
+    let id = request.get("id")?.clone();
+    Some(match result {
+        Ok(()) => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    })
+}
+
+fn call(state: &mut State, method: &str, params: &Value) -> Result<(), (i64, String)> {
+    match method {
+        // The region is kept on screen like in edit mode until it's confirmed
+        "set_selection" => {
+            let field = |name: &str| params.get(name).and_then(Value::as_f64).ok_or_else(|| (INVALID_PARAMS, format!("missing number '{}'", name)));
+            let rect = (field("x")?, field("y")?, field("width")?, field("height")?);
+            state.start_pos = None;
+            state.edit_rect = state.clamp_to_outputs(rect);
+            state.draw();
+        }
+        "set_colors" => {
+            let colors = params.as_object().ok_or_else(|| (INVALID_PARAMS, "expected an object of colors".to_string()))?;
+            for (name, value) in colors {
+                let color: Color = value.as_str().unwrap_or_default().parse().map_err(|e| (INVALID_PARAMS, e))?;
+                match name.as_str() {
+                    "background" => state.options.background_color = color,
+                    "border" => state.options.border_color = color,
+                    "selection" => state.options.selection_color = color,
+                    "box" => state.options.box_color = color,
+                    _ => return Err((INVALID_PARAMS, format!("unknown color '{}'", name))),
+                }
+            }
+            for output in &mut state.outputs {
+                output.invalidate();
+            }
+            state.draw();
+        }
+        // Same as pressing Enter
+        "confirm" => {
+            state.confirm();
+            state.draw();
+        }
+        "cancel" => state.running = false,
+        _ => return Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use wayland_client::EventQueue;
+
+use crate::cli::{self, Options};
+use crate::error::Error;
+use crate::format::FormatOptions;
+use crate::{detect_windows, event_loop, setup, Callbacks, Selection, State};
+
+// A client that connects but never sends its request is dropped after this
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+// A connected client whose request is still being read
+struct Client {
+    stream: UnixStream,
+    request: Vec<u8>,
+    connected: Instant,
+}
+
+pub fn default_socket_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    dir.join("rust-slurp.sock")
+}
+
+// Keeps the connection, outputs and buffers warm and makes a selection per request on the socket,
+// with the surfaces unmapped in between. Requests are parsed on top of the daemon's own flags.
+pub fn serve(mut defaults: Options, startup_args: Vec<OsString>) -> Result<Infallible, Error> {
+    // Between requests the default signal handlers have to stay in place
+    defaults.handle_signals = false;
+    let socket = defaults.socket.clone().unwrap_or_else(default_socket_path);
+    let listener = listen(&socket).map_err(Error::Socket)?;
+    listener.set_nonblocking(true).map_err(Error::Socket)?;
+
+    let (_conn, mut event_queue, mut state) = setup(defaults.clone(), Vec::new(), Callbacks::default(), false)?;
+    let mut clients: Vec<Client> = Vec::new();
+    loop {
+        // Output changes and pings are still handled while waiting for clients and their requests
+        event_queue.dispatch_pending(&mut state)?;
+        let fds: Vec<RawFd> = std::iter::once(listener.as_raw_fd()).chain(clients.iter().map(|c| c.stream.as_raw_fd())).collect();
+        let deadline = clients.iter().map(|c| c.connected + REQUEST_TIMEOUT).min();
+        event_loop::wait(&mut event_queue, &fds, deadline)?;
+        event_queue.dispatch_pending(&mut state)?;
+
+        loop {
+            match listener.accept().and_then(|(stream, _)| stream.set_nonblocking(true).map(|_| stream)) {
+                Ok(stream) => clients.push(Client { stream, request: Vec::new(), connected: Instant::now() }),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("Warning: failed to accept a daemon client: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let mut i = 0;
+        while i < clients.len() {
+            match read_request(&mut clients[i]) {
+                Ok(Some(request)) => {
+                    let client = clients.remove(i);
+                    handle(&client.stream, &request, &defaults, &startup_args, &mut event_queue, &mut state)?;
+                }
+                Ok(None) if clients[i].connected.elapsed() >= REQUEST_TIMEOUT => {
+                    eprintln!("Warning: a daemon client didn't send its request in time");
+                    clients.remove(i);
+                }
+                Ok(None) => i += 1,
+                Err(e) => {
+                    eprintln!("Warning: failed to read a daemon request: {}", e);
+                    clients.remove(i);
+                }
+            }
+        }
+    }
+}
+
+// A socket left behind by a daemon that didn't exit cleanly is replaced, a live one is not
+fn listen(socket: &Path) -> io::Result<UnixListener> {
+    if socket.exists() && UnixStream::connect(socket).is_err() {
+        std::fs::remove_file(socket)?;
+    }
+    UnixListener::bind(socket)
+}
+
+// Takes whatever arrived without blocking, Some once the request line is complete
+fn read_request(client: &mut Client) -> io::Result<Option<String>> {
+    let mut buf = [0; 4096];
+    loop {
+        if let Some(end) = client.request.iter().position(|&b| b == b'\n') {
+            client.request.truncate(end);
+            break;
+        }
+        if client.request.len() > MAX_REQUEST_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+        }
+        match (&client.stream).read(&mut buf) {
+            Ok(0) if client.request.is_empty() => return Err(io::Error::new(ErrorKind::UnexpectedEof, "closed without a request")),
+            // Clients that shut down their side after the request don't need the newline
+            Ok(0) => break,
+            Ok(n) => client.request.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+    String::from_utf8(std::mem::take(&mut client.request)).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+// Words are NUL-separated so flags can take values with spaces, a request without NULs is split at whitespace
+fn request_words(request: &str) -> Vec<&str> {
+    let request = request.trim_end_matches(['\r', '\0']);
+    if request.contains('\0') {
+        request.split('\0').collect()
+    } else {
+        request.split_whitespace().collect()
+    }
+}
+
+// Problems with a single client are only warned about, errors from the compositor connection end the daemon
+fn handle(stream: &UnixStream, request: &str, defaults: &Options, startup_args: &[OsString], event_queue: &mut EventQueue<State>, state: &mut State) -> Result<(), Error> {
+    // The selection can take a while, the reply is written in one go afterwards
+    if let Err(e) = stream.set_nonblocking(false) {
+        eprintln!("Warning: failed to reply to a daemon request: {}", e);
+        return Ok(());
+    }
+    let words = request_words(request);
+    let reply = match words.split_first() {
+        Some((&"select", flags)) => {
+            // Later flags win, so the request's override the daemon's and the rest are kept
+            let options = if flags.is_empty() { Ok(defaults.clone()) } else { cli::try_parse_from(startup_args.iter().cloned().chain(flags.iter().map(OsString::from))) };
+            match options {
+                Ok(options) => {
+                    let format = options.format.clone();
+                    let format_options = FormatOptions { float: options.float, pixels: options.pixels };
+                    match select(options, event_queue, state) {
+                        Ok(selections) if selections.is_empty() => "cancelled\n".to_string(),
+                        Ok(selections) => format_reply(&selections, format.as_deref(), format_options),
+                        Err(e @ (Error::Dispatch(_) | Error::Poll(_))) => {
+                            let _ = writeln!(&*stream, "error {}", e);
+                            return Err(e);
+                        }
+                        Err(e) => format!("error {}\n", e),
+                    }
+                }
+                Err(e) => format!("error {}\n", e.to_string().lines().next().unwrap_or_default()),
+            }
+        }
+        _ => "error unknown request, expected \"select\"\n".to_string(),
+    };
+    if let Err(e) = (&*stream).write_all(reply.as_bytes()) {
+        eprintln!("Warning: failed to reply to a daemon request: {}", e);
+    }
+    Ok(())
+}
+
+fn format_reply(selections: &[Selection], format: Option<&str>, format_options: FormatOptions) -> String {
+    let mut reply = "ok\n".to_string();
+    for selection in selections {
+        reply.push_str(&selection.format_with(format, format_options));
+        reply.push('\n');
+    }
+    reply
+}
+
+fn select(mut options: Options, event_queue: &mut EventQueue<State>, state: &mut State) -> Result<Vec<Selection>, Error> {
+    options.handle_signals = false;
+    let mut boxes = Vec::new();
+    if options.windows {
+        detect_windows(&mut boxes);
+    }
+    state.reset(options, boxes);
+
+    // Captured while the surfaces are still unmapped
+    let outputs: Vec<_> = state.outputs.iter().map(|o| (o.global_name, o.output.clone())).collect();
+    crate::capture_outputs(event_queue, state, &outputs)?;
+
+    state.show();
+    let deadline = state.options.timeout.map(|timeout| Instant::now() + timeout);
+    let mut result = Ok(());
+    while state.running && result.is_ok() {
+        result = event_loop::dispatch(event_queue, state, None, deadline, &[]);
+    }
+    state.hide();
+    event_queue.flush().map_err(wayland_client::DispatchError::Backend)?;
+    result.and_then(|_| state.take_results())
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use zbus::zvariant::OwnedValue;
+
+use crate::cli::{self, Options};
+use crate::error::Error;
+use crate::geometry::round_rect;
+
+pub const BUS_NAME: &str = "org.rustslurp.Selector";
+pub const OBJECT_PATH: &str = "/org/rustslurp/Selector";
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.rustslurp.Selector.Error")]
+enum SelectorError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Cancelled(String),
+    InvalidOption(String),
+    Failed(String),
+}
+
+// Requests start from the options the service was launched with, so colors and the like are
+// configured once on its command line
+struct Selector {
+    defaults: Options,
+}
+
+#[zbus::interface(name = "org.rustslurp.Selector")]
+impl Selector {
+    // Blocks the service until the user is done, requests are handled one after another
+    fn select_region(&self, options: HashMap<String, OwnedValue>) -> Result<(i32, i32, i32, i32), SelectorError> {
+        let mut request = self.defaults.clone();
+        for (key, value) in &options {
+            apply_option(&mut request, key, value)?;
+        }
+        match crate::select(request) {
+            Ok(Some(rect)) => {
+                let (x, y, w, h) = round_rect(rect);
+                Ok((x as i32, y as i32, w as i32, h as i32))
+            }
+            Ok(None) => Err(SelectorError::Cancelled("the selection was cancelled".to_string())),
+            Err(e) => Err(SelectorError::Failed(e.to_string())),
+        }
+    }
+}
+
+fn apply_option(options: &mut Options, key: &str, value: &OwnedValue) -> Result<(), SelectorError> {
+    let invalid = || SelectorError::InvalidOption(format!("invalid value for '{}'", key));
+    let flag = || value.downcast_ref::<bool>().map_err(|_| invalid());
+    match key {
+        "point" => options.point = flag()?,
+        "output" => options.output = flag()?,
+        "edit" => options.edit = flag()?,
+        "freeze" => options.freeze = flag()?,
+        "show_dimensions" => options.show_dimensions = flag()?,
+        "aspect_ratio" => {
+            let ratio = value.downcast_ref::<&str>().map_err(|_| invalid())?;
+            options.aspect_ratio = Some(cli::parse_aspect_ratio(ratio).map_err(SelectorError::InvalidOption)?);
+        }
+        "timeout" => {
+            let ms = value.downcast_ref::<u32>().map_err(|_| invalid())?;
+            options.timeout = (ms > 0).then(|| std::time::Duration::from_millis(ms as u64));
+        }
+        _ => return Err(SelectorError::InvalidOption(format!("unknown option '{}'", key))),
+    }
+    Ok(())
+}
+
+// Serves SelectRegion on the session bus until the process is killed
+pub fn serve(mut defaults: Options) -> Result<Infallible, Error> {
+    // Between requests the default signal handlers have to stay in place
+    defaults.handle_signals = false;
+    let _connection = zbus::blocking::connection::Builder::session()?.name(BUS_NAME)?.serve_at(OBJECT_PATH, Selector { defaults })?.build()?;
+    loop {
+        std::thread::park();
+    }
+}
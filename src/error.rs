@@ -0,0 +1,55 @@
+use thiserror::Error;
+
+use crate::{EXIT_ERROR, EXIT_TIMEOUT, EXIT_UNSUPPORTED};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to connect to the Wayland compositor: {0}")]
+    Connect(#[from] wayland_client::ConnectError),
+    #[error("failed to connect to the Wayland display {}: {source}", path.display())]
+    Display { path: std::path::PathBuf, source: std::io::Error },
+    #[error("failed to list the compositor's globals: {0}")]
+    Registry(#[from] wayland_client::globals::GlobalError),
+    #[error("lost the connection to the compositor: {0}")]
+    Dispatch(#[from] wayland_client::DispatchError),
+    #[error("your compositor does not support the required Wayland protocols, missing: {0}")]
+    MissingProtocols(String),
+    #[error("failed to allocate a shared memory buffer: {0}")]
+    Buffer(#[from] std::io::Error),
+    #[error("failed to set up signal handling: {0}")]
+    Signals(std::io::Error),
+    #[error("waiting for compositor events failed: {0}")]
+    Poll(std::io::Error),
+    #[error("drawing the overlay failed: {0}")]
+    Cairo(#[from] cairo::Error),
+    #[error("all outputs were removed")]
+    NoOutputs,
+    #[error("none of the outputs {0} exist")]
+    UnknownOutputs(String),
+    #[error("failed to fork the clipboard process: {0}")]
+    Fork(std::io::Error),
+    #[error("failed to save the capture to {}: {source}", path.display())]
+    SaveCapture { path: std::path::PathBuf, source: cairo::IoError },
+    #[cfg(feature = "dbus")]
+    #[error("D-Bus service failed: {0}")]
+    DBus(#[from] zbus::Error),
+    #[cfg(feature = "gpu")]
+    #[error("OpenGL ES rendering failed: {0}")]
+    Gpu(String),
+    #[error("failed to listen on the daemon socket: {0}")]
+    Socket(std::io::Error),
+    #[error("failed to read control commands: {0}")]
+    Control(std::io::Error),
+    #[error("no selection was made before the timeout")]
+    Timeout,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::MissingProtocols(_) => EXIT_UNSUPPORTED,
+            Error::Timeout => EXIT_TIMEOUT,
+            _ => EXIT_ERROR,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
+
+use wayland_client::backend::WaylandError;
+use wayland_client::{DispatchError, EventQueue};
+
+use crate::error::Error;
+use crate::signals::{Signal, Signals};
+use crate::State;
+
+// Like blocking_dispatch, but also wakes up for signals, when the deadline passes and when one of
+// the other fds becomes readable, which the caller checks afterwards
+pub fn dispatch(event_queue: &mut EventQueue<State>, state: &mut State, signals: Option<&Signals>, deadline: Option<Instant>, other_fds: &[RawFd]) -> Result<(), Error> {
+    event_queue.dispatch_pending(state)?;
+    if !state.running {
+        return Ok(());
+    }
+    let mut fds = other_fds.to_vec();
+    if let Some(signals) = signals {
+        fds.extend(signals.fds());
+    }
+    wait(event_queue, &fds, deadline)?;
+
+    match signals.and_then(|s| s.take()) {
+        Some(Signal::Cancel) => state.running = false,
+        Some(Signal::Confirm) => {
+            state.confirm();
+            state.draw();
+        }
+        None => {}
+    }
+    event_queue.dispatch_pending(state)?;
+
+    if state.running && deadline.is_some_and(|d| Instant::now() >= d) {
+        state.fail(Error::Timeout);
+    }
+    Ok(())
+}
+
+// Flushes requests, then blocks until compositor events arrive, one of the other fds becomes
+// readable or the deadline passes. Compositor events are read but left for dispatch_pending.
+pub fn wait(event_queue: &mut EventQueue<State>, other_fds: &[RawFd], deadline: Option<Instant>) -> Result<(), Error> {
+    event_queue.flush().map_err(DispatchError::Backend)?;
+
+    // Events may have been queued by another reader since dispatching, in which case there's no guard
+    if let Some(guard) = event_queue.prepare_read() {
+        let mut fds = vec![pollfd(guard.connection_fd().as_raw_fd())];
+        fds.extend(other_fds.iter().copied().map(pollfd));
+        // Rounded up so the poll doesn't return just before the deadline
+        let timeout = deadline.map_or(-1, |d| d.saturating_duration_since(Instant::now()).as_micros().div_ceil(1000).min(i32::MAX as u128) as i32);
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } < 0 {
+            let e = std::io::Error::last_os_error();
+            // A signal interrupted the poll, its byte is read by the caller
+            if e.kind() != ErrorKind::Interrupted {
+                return Err(Error::Poll(e));
+            }
+        } else if fds[0].revents != 0 {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(DispatchError::Backend(e).into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn pollfd(fd: RawFd) -> libc::pollfd {
+    libc::pollfd { fd, events: libc::POLLIN, revents: 0 }
+}
@@ -0,0 +1,204 @@
+use crate::geometry::round_rect;
+use crate::Rect;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Literal(String),
+    Field(char),
+}
+
+pub struct FormatContext<'a> {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub output_x: i32,
+    pub output_y: i32,
+    pub output_scale: f64,
+    pub output_name: Option<&'a str>,
+    pub label: Option<&'a str>,
+    pub app_id: Option<&'a str>,
+    pub title: Option<&'a str>,
+}
+
+// How the geometry is printed, set by --float and --pixels on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FormatOptions {
+    // Prints the geometry unrounded, otherwise the edges are rounded to whole pixels
+    pub float: bool,
+    // Prints the geometry in buffer pixels of the output, relative to its top left corner
+    pub pixels: bool,
+}
+
+pub const DEFAULT_FORMAT: &str = "%x,%y %wx%h";
+pub const DEFAULT_LABELED_FORMAT: &str = "%x,%y %wx%h %l";
+// The default format with --print-output
+pub const OUTPUT_FORMAT: &str = "%x,%y %wx%h %o";
+// Output-local geometry followed by the output, e.g. for wf-recorder -o NAME -g GEOMETRY
+pub const RECORDER_FORMAT: &str = "%X,%Y %wx%h %o %s";
+
+// --exec hands fields to the shell as environment variables, so titles and labels are never parsed as shell code
+const SHELL_VARIABLES: [(char, &str); 14] = [
+    ('g', "SLURP_GEOMETRY"),
+    ('x', "SLURP_X"),
+    ('y', "SLURP_Y"),
+    ('w', "SLURP_WIDTH"),
+    ('h', "SLURP_HEIGHT"),
+    ('X', "SLURP_OUTPUT_X"),
+    ('Y', "SLURP_OUTPUT_Y"),
+    ('W', "SLURP_PIXEL_WIDTH"),
+    ('H', "SLURP_PIXEL_HEIGHT"),
+    ('o', "SLURP_OUTPUT"),
+    ('s', "SLURP_OUTPUT_SCALE"),
+    ('l', "SLURP_LABEL"),
+    ('a', "SLURP_APP_ID"),
+    ('t', "SLURP_TITLE"),
+];
+
+pub fn parse(format: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => literal.push('%'),
+            Some(field @ ('g' | 'x' | 'y' | 'w' | 'h' | 'X' | 'Y' | 'W' | 'H' | 'o' | 's' | 'l' | 'a' | 't')) => {
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Field(field));
+            }
+            // Unknown directives are printed as-is
+            Some(other) => {
+                literal.push('%');
+                literal.push(other);
+            }
+            None => literal.push('%'),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    tokens
+}
+
+// Inverse of parse, percent signs in literals are escaped again
+pub fn unparse(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Literal(s) => out.push_str(&s.replace('%', "%%")),
+            Token::Field(field) => {
+                out.push('%');
+                out.push(*field);
+            }
+        }
+    }
+    out
+}
+
+// Adding zero turns -0 into 0
+fn number(n: f64) -> String {
+    (n + 0.0).to_string()
+}
+
+pub fn expand(tokens: &[Token], ctx: &FormatContext) -> String {
+    expand_with(tokens, ctx, (ctx.x as f64, ctx.y as f64, ctx.width as f64, ctx.height as f64), FormatOptions::default())
+}
+
+// Like expand, with the unrounded rect in place of the geometry of the context
+pub fn expand_with(tokens: &[Token], ctx: &FormatContext, rect: Rect, options: FormatOptions) -> String {
+    let (output_x, output_y) = (ctx.output_x as f64, ctx.output_y as f64);
+    let (rect, output_pos, scale) = match options.pixels {
+        true => {
+            let s = ctx.output_scale;
+            (((rect.0 - output_x) * s, (rect.1 - output_y) * s, rect.2 * s, rect.3 * s), (0.0, 0.0), 1.0)
+        }
+        false => (rect, (output_x, output_y), ctx.output_scale),
+    };
+    let (x, y, width, height) = if options.float { rect } else { round_rect(rect) };
+    let pixels = |n: f64| if options.float { n * scale } else { (n * scale).round() };
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Literal(s) => out.push_str(s),
+            Token::Field('g') => out.push_str(&format!("{},{} {}x{}", number(x), number(y), number(width), number(height))),
+            Token::Field('x') => out.push_str(&number(x)),
+            Token::Field('y') => out.push_str(&number(y)),
+            Token::Field('w') => out.push_str(&number(width)),
+            Token::Field('h') => out.push_str(&number(height)),
+            Token::Field('X') => out.push_str(&number(x - output_pos.0)),
+            Token::Field('Y') => out.push_str(&number(y - output_pos.1)),
+            Token::Field('W') => out.push_str(&number(pixels(width))),
+            Token::Field('H') => out.push_str(&number(pixels(height))),
+            Token::Field('s') => out.push_str(&ctx.output_scale.to_string()),
+            Token::Field('o') => out.push_str(ctx.output_name.unwrap_or("<unknown>")),
+            Token::Field('l') => out.push_str(ctx.label.unwrap_or("")),
+            Token::Field('a') => out.push_str(ctx.app_id.unwrap_or("")),
+            Token::Field('t') => out.push_str(ctx.title.unwrap_or("")),
+            Token::Field(_) => {}
+        }
+    }
+    out
+}
+
+#[derive(Clone, Copy)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+// Turns fields into references to the variables from shell_variables. Single quotes are closed around a
+// reference and reopened after it, so fields expand wherever they appear in the command.
+pub fn expand_for_shell(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut quoting = Quoting::None;
+    let mut escaped = false;
+    for token in tokens {
+        match token {
+            Token::Literal(s) => {
+                for c in s.chars() {
+                    out.push(c);
+                    if escaped {
+                        escaped = false;
+                        continue;
+                    }
+                    quoting = match (quoting, c) {
+                        (Quoting::None | Quoting::Double, '\\') => {
+                            escaped = true;
+                            quoting
+                        }
+                        (Quoting::None, '\'') => Quoting::Single,
+                        (Quoting::None, '"') => Quoting::Double,
+                        (Quoting::Single, '\'') | (Quoting::Double, '"') => Quoting::None,
+                        _ => quoting,
+                    };
+                }
+            }
+            Token::Field(field) => {
+                escaped = false;
+                if let Some((_, name)) = SHELL_VARIABLES.iter().find(|(f, _)| f == field) {
+                    match quoting {
+                        Quoting::Single => out.push_str(&format!("'\"${{{}}}\"'", name)),
+                        _ => out.push_str(&format!("${{{}}}", name)),
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+pub fn shell_variables(ctx: &FormatContext) -> Vec<(&'static str, String)> {
+    shell_variables_with(ctx, (ctx.x as f64, ctx.y as f64, ctx.width as f64, ctx.height as f64), FormatOptions::default())
+}
+
+pub fn shell_variables_with(ctx: &FormatContext, rect: Rect, options: FormatOptions) -> Vec<(&'static str, String)> {
+    SHELL_VARIABLES.iter().map(|&(field, name)| (name, expand_with(&[Token::Field(field)], ctx, rect, options))).collect()
+}
@@ -0,0 +1,345 @@
+use wayland_client::protocol::wl_output;
+
+use crate::Rect;
+
+pub(crate) fn transform_swaps_axes(transform: wl_output::Transform) -> bool {
+    matches!(
+        transform,
+        wl_output::Transform::_90 | wl_output::Transform::_270 | wl_output::Transform::Flipped90 | wl_output::Transform::Flipped270
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+    // The whole rectangle, grabbed from inside or with Alt
+    Move,
+}
+
+pub(crate) fn snap_to_grid(pos: (f64, f64), grid: Option<f64>) -> (f64, f64) {
+    match grid {
+        Some(n) => ((pos.0 / n).round() * n, (pos.1 / n).round() * n),
+        None => pos,
+    }
+}
+
+// Surface-local logical coordinates on the output at output_pos to global ones, and back
+pub(crate) fn to_global((x, y): (f64, f64), (ox, oy): (i32, i32)) -> (f64, f64) {
+    (ox as f64 + x, oy as f64 + y)
+}
+
+pub(crate) fn to_local((x, y): (f64, f64), (ox, oy): (i32, i32)) -> (f64, f64) {
+    (x - ox as f64, y - oy as f64)
+}
+
+// Rounds the edges rather than position and size, so selections sharing an edge still do afterwards
+pub(crate) fn round_rect((x, y, w, h): Rect) -> Rect {
+    let (x0, y0) = (x.round(), y.round());
+    (x0, y0, (x + w).round() - x0, (y + h).round() - y0)
+}
+
+pub(crate) fn grow_rect((x, y, w, h): Rect, margin: f64) -> Rect {
+    (x - margin, y - margin, w + 2.0 * margin, h + 2.0 * margin)
+}
+
+// The buffer pixels covering a rectangle in output-local logical coordinates
+pub(crate) fn buffer_rect((x, y, w, h): Rect, (sx, sy): (f64, f64)) -> (i32, i32, i32, i32) {
+    let (x0, y0) = ((x * sx).floor(), (y * sy).floor());
+    let (x1, y1) = (((x + w) * sx).ceil(), ((y + h) * sy).ceil());
+    (x0 as i32, y0 as i32, (x1 - x0) as i32, (y1 - y0) as i32)
+}
+
+pub(crate) fn intersect_rect(a: (f64, f64, f64, f64), b: (f64, f64, f64, f64)) -> Option<(f64, f64, f64, f64)> {
+    let x0 = a.0.max(b.0);
+    let y0 = a.1.max(b.1);
+    let x1 = (a.0 + a.2).min(b.0 + b.2);
+    let y1 = (a.1 + a.3).min(b.1 + b.3);
+    if x1 > x0 && y1 > y0 {
+        Some((x0, y0, x1 - x0, y1 - y0))
+    } else {
+        None
+    }
+}
+
+// Moves the corners into the bounds, unlike intersect_rect this keeps empty and outside rectangles
+pub(crate) fn clamp_rect((x, y, w, h): Rect, (bx, by, bw, bh): Rect) -> Rect {
+    let (x0, y0) = (x.clamp(bx, bx + bw), y.clamp(by, by + bh));
+    let (x1, y1) = ((x + w).clamp(bx, bx + bw), (y + h).clamp(by, by + bh));
+    (x0, y0, x1 - x0, y1 - y0)
+}
+
+// Moves the rectangle into the bounds without resizing it, as far as it fits
+pub(crate) fn shift_into((x, y, w, h): Rect, (bx, by, bw, bh): Rect) -> Rect {
+    (x.min(bx + bw - w).max(bx), y.min(by + bh - h).max(by), w, h)
+}
+
+// Smallest rectangle covering all of the given ones, e.g. the whole output layout
+pub(crate) fn bounding_rect(rects: impl IntoIterator<Item = Rect>) -> Option<Rect> {
+    rects.into_iter().reduce(|a, b| {
+        let x = a.0.min(b.0);
+        let y = a.1.min(b.1);
+        (x, y, (a.0 + a.2).max(b.0 + b.2) - x, (a.1 + a.3).max(b.1 + b.3) - y)
+    })
+}
+
+// Keeps a point on the rectangle's pixels, the right and bottom edges are exclusive like in rect_contains
+pub(crate) fn clamp_point((x, y): (f64, f64), rect: Rect) -> (f64, f64) {
+    (x.min(rect.0 + rect.2 - 1.0).max(rect.0), y.min(rect.1 + rect.3 - 1.0).max(rect.1))
+}
+
+pub(crate) fn handle_points(rect: (f64, f64, f64, f64)) -> [(f64, f64); 8] {
+    let (x, y, w, h) = rect;
+    [
+        (x, y),
+        (x + w / 2.0, y),
+        (x + w, y),
+        (x + w, y + h / 2.0),
+        (x + w, y + h),
+        (x + w / 2.0, y + h),
+        (x, y + h),
+        (x, y + h / 2.0),
+    ]
+}
+
+pub(crate) fn rect_contains(rect: (f64, f64, f64, f64), pos: (f64, f64)) -> bool {
+    pos.0 >= rect.0 && pos.1 >= rect.1 && pos.0 < rect.0 + rect.2 && pos.1 < rect.1 + rect.3
+}
+
+// Corners win over edges so small selections can still be resized diagonally
+pub(crate) fn handle_at(rect: (f64, f64, f64, f64), pos: (f64, f64), tolerance: f64) -> Option<Handle> {
+    let (x, y, w, h) = rect;
+    let near = |a: f64, b: f64| (a - b).abs() <= tolerance;
+    let inside_x = pos.0 >= x - tolerance && pos.0 <= x + w + tolerance;
+    let inside_y = pos.1 >= y - tolerance && pos.1 <= y + h + tolerance;
+    if !inside_x || !inside_y {
+        return None;
+    }
+
+    let (left, right) = (near(pos.0, x), near(pos.0, x + w));
+    let (top, bottom) = (near(pos.1, y), near(pos.1, y + h));
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Some(Handle::TopLeft),
+        (_, true, true, _) => Some(Handle::TopRight),
+        (_, true, _, true) => Some(Handle::BottomRight),
+        (true, _, _, true) => Some(Handle::BottomLeft),
+        (true, _, _, _) => Some(Handle::Left),
+        (_, true, _, _) => Some(Handle::Right),
+        (_, _, true, _) => Some(Handle::Top),
+        (_, _, _, true) => Some(Handle::Bottom),
+        _ => None,
+    }
+}
+
+// Moves the edges attached to the handle, flipping the rectangle if it's dragged past the opposite edge
+pub(crate) fn resize_rect(rect: (f64, f64, f64, f64), handle: Handle, dx: f64, dy: f64) -> (f64, f64, f64, f64) {
+    let (mut x0, mut y0) = (rect.0, rect.1);
+    let (mut x1, mut y1) = (rect.0 + rect.2, rect.1 + rect.3);
+    match handle {
+        Handle::TopLeft | Handle::Left | Handle::BottomLeft => x0 += dx,
+        Handle::TopRight | Handle::Right | Handle::BottomRight => x1 += dx,
+        Handle::Top | Handle::Bottom => {}
+        Handle::Move => (x0, x1) = (x0 + dx, x1 + dx),
+    }
+    match handle {
+        Handle::TopLeft | Handle::Top | Handle::TopRight => y0 += dy,
+        Handle::BottomLeft | Handle::Bottom | Handle::BottomRight => y1 += dy,
+        Handle::Left | Handle::Right => {}
+        Handle::Move => (y0, y1) = (y0 + dy, y1 + dy),
+    }
+    (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SelectionAnchor {
+    // The press point is one corner of the box
+    Corner,
+    // The press point is the center of the box
+    Center,
+}
+
+pub(crate) fn get_selection_box(p1: (f64, f64), p2: (f64, f64), aspect_ratio: Option<f64>, anchor: SelectionAnchor) -> (f64, f64, f64, f64) {
+    let scale = if anchor == SelectionAnchor::Center { 2.0 } else { 1.0 };
+    let mut w = (p1.0 - p2.0).abs() * scale;
+    let mut h = (p1.1 - p2.1).abs() * scale;
+
+    // Grow the shorter side so the box keeps the requested ratio
+    if let Some(ratio) = aspect_ratio {
+        if w > h * ratio {
+            h = w / ratio;
+        } else {
+            w = h * ratio;
+        }
+    }
+
+    match anchor {
+        SelectionAnchor::Center => (p1.0 - w / 2.0, p1.1 - h / 2.0, w, h),
+        SelectionAnchor::Corner => {
+            let x = if p2.0 < p1.0 { p1.0 - w } else { p1.0 };
+            let y = if p2.1 < p1.1 { p1.1 - h } else { p1.1 };
+            (x, y, w, h)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamping_moves_corners_inside() {
+        let bounds = (0.0, 0.0, 1920.0, 1080.0);
+        assert_eq!(clamp_rect((1800.0, 1000.0, 300.0, 200.0), bounds), (1800.0, 1000.0, 120.0, 80.0));
+        assert_eq!(clamp_rect((-10.0, 10.0, 20.0, 0.0), bounds), (0.0, 10.0, 10.0, 0.0));
+        assert_eq!(clamp_rect((2000.0, 10.0, 20.0, 20.0), bounds), (1920.0, 10.0, 0.0, 20.0));
+    }
+
+    #[test]
+    fn moving_keeps_the_size() {
+        let bounds = (0.0, 0.0, 1920.0, 1080.0);
+        let moved = resize_rect((100.0, 100.0, 200.0, 50.0), Handle::Move, 1800.0, -150.0);
+        assert_eq!(moved, (1900.0, -50.0, 200.0, 50.0));
+        assert_eq!(shift_into(moved, bounds), (1720.0, 0.0, 200.0, 50.0));
+    }
+
+    #[test]
+    fn rounding_keeps_shared_edges() {
+        let left = round_rect((10.4, 20.6, 30.3, 40.0));
+        let right = round_rect((40.7, 20.6, 5.0, 40.0));
+        assert_eq!(left, (10.0, 21.0, 31.0, 40.0));
+        assert_eq!(left.0 + left.2, right.0);
+        assert_eq!(round_rect((-0.4, -1.6, 1.0, 1.0)), (0.0, -2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn selection_box_from_any_drag_direction() {
+        let expected = (10.0, 20.0, 30.0, 40.0);
+        assert_eq!(get_selection_box((10.0, 20.0), (40.0, 60.0), None, SelectionAnchor::Corner), expected);
+        assert_eq!(get_selection_box((40.0, 60.0), (10.0, 20.0), None, SelectionAnchor::Corner), expected);
+        assert_eq!(get_selection_box((40.0, 20.0), (10.0, 60.0), None, SelectionAnchor::Corner), expected);
+        assert_eq!(get_selection_box((10.0, 60.0), (40.0, 20.0), None, SelectionAnchor::Corner), expected);
+    }
+
+    #[test]
+    fn selection_box_with_negative_coordinates() {
+        assert_eq!(get_selection_box((-100.0, -50.0), (-20.0, 10.0), None, SelectionAnchor::Corner), (-100.0, -50.0, 80.0, 60.0));
+        assert_eq!(get_selection_box((-20.0, 10.0), (-100.0, -50.0), None, SelectionAnchor::Corner), (-100.0, -50.0, 80.0, 60.0));
+    }
+
+    #[test]
+    fn zero_size_drag() {
+        assert_eq!(get_selection_box((5.0, 5.0), (5.0, 5.0), None, SelectionAnchor::Corner), (5.0, 5.0, 0.0, 0.0));
+        assert_eq!(get_selection_box((5.0, 5.0), (5.0, 5.0), Some(2.0), SelectionAnchor::Center), (5.0, 5.0, 0.0, 0.0));
+        assert_eq!(get_selection_box((5.0, 5.0), (25.0, 5.0), None, SelectionAnchor::Corner), (5.0, 5.0, 20.0, 0.0));
+    }
+
+    #[test]
+    fn selection_box_keeps_aspect_ratio() {
+        // The shorter side grows, towards where the pointer is
+        assert_eq!(get_selection_box((0.0, 0.0), (40.0, 10.0), Some(2.0), SelectionAnchor::Corner), (0.0, 0.0, 40.0, 20.0));
+        assert_eq!(get_selection_box((0.0, 0.0), (10.0, 40.0), Some(2.0), SelectionAnchor::Corner), (0.0, 0.0, 80.0, 40.0));
+        assert_eq!(get_selection_box((0.0, 0.0), (-40.0, -10.0), Some(2.0), SelectionAnchor::Corner), (-40.0, -20.0, 40.0, 20.0));
+    }
+
+    #[test]
+    fn selection_box_around_center() {
+        assert_eq!(get_selection_box((50.0, 50.0), (60.0, 45.0), None, SelectionAnchor::Center), (40.0, 45.0, 20.0, 10.0));
+        assert_eq!(get_selection_box((50.0, 50.0), (60.0, 45.0), Some(1.0), SelectionAnchor::Center), (40.0, 40.0, 20.0, 20.0));
+    }
+
+    #[test]
+    fn coordinates_on_outputs_left_of_and_above_the_origin() {
+        assert_eq!(to_global((10.0, 20.0), (-1920, -1080)), (-1910.0, -1060.0));
+        assert_eq!(to_local((-1910.0, -1060.0), (-1920, -1080)), (10.0, 20.0));
+        assert_eq!(to_local((100.5, 0.0), (1920, 0)), (-1819.5, 0.0));
+        assert_eq!(to_global(to_local((123.25, -7.5), (-640, 480)), (-640, 480)), (123.25, -7.5));
+    }
+
+    #[test]
+    fn intersection() {
+        assert_eq!(intersect_rect((0.0, 0.0, 100.0, 100.0), (50.0, 50.0, 100.0, 100.0)), Some((50.0, 50.0, 50.0, 50.0)));
+        assert_eq!(intersect_rect((-100.0, -100.0, 150.0, 150.0), (0.0, 0.0, 1920.0, 1080.0)), Some((0.0, 0.0, 50.0, 50.0)));
+        // Touching edges and empty rectangles don't overlap
+        assert_eq!(intersect_rect((0.0, 0.0, 100.0, 100.0), (100.0, 0.0, 100.0, 100.0)), None);
+        assert_eq!(intersect_rect((10.0, 10.0, 0.0, 0.0), (0.0, 0.0, 100.0, 100.0)), None);
+    }
+
+    #[test]
+    fn bounds_of_outputs_around_the_origin() {
+        let outputs = [(0.0, 0.0, 1920.0, 1080.0), (-1280.0, -200.0, 1280.0, 1024.0), (1920.0, 0.0, 1080.0, 1920.0)];
+        assert_eq!(bounding_rect(outputs), Some((-1280.0, -200.0, 4280.0, 2120.0)));
+        assert_eq!(bounding_rect([(-10.0, -10.0, 5.0, 5.0)]), Some((-10.0, -10.0, 5.0, 5.0)));
+        assert_eq!(bounding_rect(Vec::<Rect>::new()), None);
+    }
+
+    #[test]
+    fn clamping_to_the_layout() {
+        let layout = bounding_rect([(-1920.0, 0.0, 1920.0, 1080.0), (0.0, 0.0, 1920.0, 1080.0)]).unwrap();
+        assert_eq!(intersect_rect((-2000.0, -100.0, 500.0, 300.0), layout), Some((-1920.0, 0.0, 420.0, 200.0)));
+        assert_eq!(intersect_rect((4000.0, 0.0, 10.0, 10.0), layout), None);
+
+        let output = (-1920.0, -1080.0, 1920.0, 1080.0);
+        assert_eq!(clamp_point((-2000.0, 5.0), output), (-1920.0, -1.0));
+        assert_eq!(clamp_point((0.0, -1080.0), output), (-1.0, -1080.0));
+        assert_eq!(clamp_point((-10.5, -20.5), output), (-10.5, -20.5));
+    }
+
+    #[test]
+    fn containment_excludes_right_and_bottom_edges() {
+        let rect = (-100.0, -100.0, 100.0, 100.0);
+        assert!(rect_contains(rect, (-100.0, -100.0)));
+        assert!(rect_contains(rect, (-0.5, -0.5)));
+        assert!(!rect_contains(rect, (0.0, -50.0)));
+        assert!(!rect_contains(rect, (-50.0, 0.0)));
+    }
+
+    #[test]
+    fn snapping_to_the_grid() {
+        assert_eq!(snap_to_grid((14.0, 16.0), Some(10.0)), (10.0, 20.0));
+        assert_eq!(snap_to_grid((-14.0, -16.0), Some(10.0)), (-10.0, -20.0));
+        assert_eq!(snap_to_grid((-1925.0, 3.0), Some(8.0)), (-1928.0, 0.0));
+        assert_eq!(snap_to_grid((14.5, -3.25), None), (14.5, -3.25));
+    }
+
+    #[test]
+    fn buffer_rect_covers_partial_pixels() {
+        assert_eq!(buffer_rect((10.0, 20.0, 30.0, 40.0), (1.0, 1.0)), (10, 20, 30, 40));
+        assert_eq!(buffer_rect((10.0, 20.0, 30.0, 40.0), (2.0, 2.0)), (20, 40, 60, 80));
+        assert_eq!(buffer_rect((0.5, 0.5, 1.0, 1.0), (1.5, 1.5)), (0, 0, 3, 3));
+        assert_eq!(buffer_rect((-2.5, -2.5, 5.0, 5.0), (1.0, 1.0)), (-3, -3, 6, 6));
+    }
+
+    #[test]
+    fn handles_prefer_corners() {
+        let rect = (0.0, 0.0, 10.0, 10.0);
+        assert_eq!(handle_at(rect, (1.0, 1.0), 8.0), Some(Handle::TopLeft));
+        assert_eq!(handle_at(rect, (9.0, 9.0), 8.0), Some(Handle::BottomRight));
+        assert_eq!(handle_at((0.0, 0.0, 100.0, 100.0), (50.0, 2.0), 8.0), Some(Handle::Top));
+        assert_eq!(handle_at((0.0, 0.0, 100.0, 100.0), (-5.0, 50.0), 8.0), Some(Handle::Left));
+        assert_eq!(handle_at((0.0, 0.0, 100.0, 100.0), (50.0, 50.0), 8.0), None);
+        assert_eq!(handle_at((0.0, 0.0, 100.0, 100.0), (-20.0, 50.0), 8.0), None);
+    }
+
+    #[test]
+    fn resizing_flips_past_the_opposite_edge() {
+        let rect = (10.0, 10.0, 20.0, 20.0);
+        assert_eq!(resize_rect(rect, Handle::BottomRight, 5.0, -5.0), (10.0, 10.0, 25.0, 15.0));
+        assert_eq!(resize_rect(rect, Handle::Left, -10.0, 100.0), (0.0, 10.0, 30.0, 20.0));
+        assert_eq!(resize_rect(rect, Handle::Right, -30.0, 0.0), (0.0, 10.0, 10.0, 20.0));
+        assert_eq!(resize_rect(rect, Handle::TopLeft, 30.0, 30.0), (30.0, 30.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn rotated_outputs_swap_axes() {
+        assert!(transform_swaps_axes(wl_output::Transform::_90));
+        assert!(transform_swaps_axes(wl_output::Transform::Flipped270));
+        assert!(!transform_swaps_axes(wl_output::Transform::Normal));
+        assert!(!transform_swaps_axes(wl_output::Transform::_180));
+    }
+}
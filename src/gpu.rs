@@ -0,0 +1,340 @@
+use std::rc::Rc;
+
+use cairo::{Context, Filter, Format, ImageSurface};
+use glow::HasContext;
+use khronos_egl as egl;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::{Connection, Proxy, QueueHandle};
+use wayland_egl::WlEglSurface;
+
+use crate::color::Color;
+use crate::render::{Background, BufferRect, RenderTarget, Renderer};
+use crate::wayland::OutputState;
+use crate::{Error, State};
+
+const VERTEX_SHADER: &str = "
+attribute vec2 corner;
+uniform vec4 rect;
+uniform vec2 size;
+varying vec2 uv;
+varying vec2 local;
+void main() {
+    uv = corner;
+    local = (corner - 0.5) * rect.zw;
+    vec2 pos = (rect.xy + corner * rect.zw) / size * 2.0 - 1.0;
+    gl_Position = vec4(pos.x, -pos.y, 0.0, 1.0);
+}
+";
+
+// Rounded rectangles with antialiased edges, the coverage ends up in every channel
+const FILL_SHADER: &str = "
+precision mediump float;
+uniform vec4 color;
+uniform vec4 rect;
+uniform float radius;
+varying vec2 local;
+void main() {
+    vec2 q = abs(local) - rect.zw * 0.5 + radius;
+    float d = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+    gl_FragColor = color * clamp(0.5 - d, 0.0, 1.0);
+}
+";
+
+// Cairo's ARGB32 is BGRA in memory, which is uploaded as RGBA since GLES2 has no BGRA without extensions
+const TEXTURE_SHADER: &str = "
+precision mediump float;
+uniform sampler2D image;
+varying vec2 uv;
+void main() {
+    gl_FragColor = texture2D(image, uv).bgra;
+}
+";
+
+// One EGL context draws the surfaces of every output. Their buffers come from the EGL driver through
+// wayland-egl, which shares them with the compositor over linux-dmabuf or wl_drm on its own.
+pub(crate) struct GpuRenderer {
+    gl: Rc<Gl>,
+}
+
+struct Gl {
+    egl: egl::Instance<egl::Static>,
+    display: egl::Display,
+    config: egl::Config,
+    context: egl::Context,
+    gl: glow::Context,
+    fill_program: glow::Program,
+    texture_program: glow::Program,
+    quad: glow::Buffer,
+}
+
+// The overlay is drawn by cairo into memory and uploaded, the background and selection fills are drawn by GL underneath
+struct GpuSurface {
+    gl: Rc<Gl>,
+    surface: egl::Surface,
+    _window: WlEglSurface,
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+    context: Context,
+    overlay: glow::Texture,
+    frozen: Option<glow::Texture>,
+    invalid: bool,
+    // Rects of the overlay drawn since it was last uploaded, None when all of it was
+    uploads: Option<Vec<BufferRect>>,
+}
+
+fn gpu_error(what: &str) -> impl Fn(egl::Error) -> Error + '_ {
+    move |e| Error::Gpu(format!("{} failed: {}", what, e))
+}
+
+impl GpuRenderer {
+    pub fn new(conn: &Connection) -> Result<Self, Error> {
+        let egl = egl::Instance::new(egl::Static);
+        let display = unsafe { egl.get_display(conn.backend().display_ptr() as egl::NativeDisplayType) }.ok_or_else(|| Error::Gpu("no EGL display for the Wayland connection".to_string()))?;
+        egl.initialize(display).map_err(gpu_error("eglInitialize"))?;
+        egl.bind_api(egl::OPENGL_ES_API).map_err(gpu_error("eglBindAPI"))?;
+
+        #[rustfmt::skip]
+        let config_attributes = [
+            egl::RED_SIZE, 8, egl::GREEN_SIZE, 8, egl::BLUE_SIZE, 8, egl::ALPHA_SIZE, 8,
+            egl::SURFACE_TYPE, egl::WINDOW_BIT,
+            egl::RENDERABLE_TYPE, egl::OPENGL_ES2_BIT,
+            egl::NONE,
+        ];
+        let config = egl
+            .choose_first_config(display, &config_attributes)
+            .map_err(gpu_error("eglChooseConfig"))?
+            .ok_or_else(|| Error::Gpu("no EGL config with alpha for OpenGL ES 2".to_string()))?;
+        let context = egl.create_context(display, config, None, &[egl::CONTEXT_CLIENT_VERSION, 2, egl::NONE]).map_err(gpu_error("eglCreateContext"))?;
+        egl.make_current(display, None, None, Some(context)).map_err(gpu_error("eglMakeCurrent"))?;
+
+        let gl = unsafe { glow::Context::from_loader_function(|name| egl.get_proc_address(name).map_or(std::ptr::null(), |f| f as *const _)) };
+        let fill_program = unsafe { link_program(&gl, FILL_SHADER)? };
+        let texture_program = unsafe { link_program(&gl, TEXTURE_SHADER)? };
+        let quad = unsafe {
+            let quad = gl.create_buffer().map_err(Error::Gpu)?;
+            let corners: Vec<u8> = [0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0].iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, &corners, glow::STATIC_DRAW);
+            quad
+        };
+
+        Ok(Self { gl: Rc::new(Gl { egl, display, config, context, gl, fill_program, texture_program, quad }) })
+    }
+}
+
+unsafe fn link_program(gl: &glow::Context, fragment_source: &str) -> Result<glow::Program, Error> {
+    let program = gl.create_program().map_err(Error::Gpu)?;
+    for (kind, source) in [(glow::VERTEX_SHADER, VERTEX_SHADER), (glow::FRAGMENT_SHADER, fragment_source)] {
+        let shader = gl.create_shader(kind).map_err(Error::Gpu)?;
+        gl.shader_source(shader, source);
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            return Err(Error::Gpu(format!("compiling a shader failed: {}", gl.get_shader_info_log(shader))));
+        }
+        gl.attach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+    gl.bind_attrib_location(program, 0, "corner");
+    gl.link_program(program);
+    if !gl.get_program_link_status(program) {
+        return Err(Error::Gpu(format!("linking a shader failed: {}", gl.get_program_info_log(program))));
+    }
+    Ok(program)
+}
+
+impl Renderer for GpuRenderer {
+    // Surfaces don't share memory, so only the output's own is replaced
+    fn allocate(&mut self, outputs: &mut [OutputState], index: usize, _: &QueueHandle<State>) -> Result<bool, Error> {
+        let output_state = &mut outputs[index];
+        if let Some(mut old) = output_state.target.take() {
+            old.destroy();
+        }
+        output_state.drawn_regions.clear();
+        let Some((width, height)) = output_state.buffer_size() else {
+            return Ok(false);
+        };
+        output_state.target = Some(Box::new(GpuSurface::new(self.gl.clone(), &output_state.surface, width, height)?));
+        Ok(false)
+    }
+}
+
+impl GpuSurface {
+    fn new(gl: Rc<Gl>, wl_surface: &WlSurface, width: i32, height: i32) -> Result<Self, Error> {
+        let window = WlEglSurface::new(wl_surface.id(), width, height).map_err(|e| Error::Gpu(format!("creating the EGL window failed: {}", e)))?;
+        let surface = unsafe { gl.egl.create_window_surface(gl.display, gl.config, window.ptr() as egl::NativeWindowType, None) }.map_err(gpu_error("eglCreateWindowSurface"))?;
+        gl.egl.make_current(gl.display, Some(surface), Some(surface), Some(gl.context)).map_err(gpu_error("eglMakeCurrent"))?;
+        // Frames are throttled by frame callbacks, swapping must not wait for them
+        gl.egl.swap_interval(gl.display, 0).map_err(gpu_error("eglSwapInterval"))?;
+
+        let stride = Format::ARgb32.stride_for_width(width as u32)?;
+        let mut pixels = vec![0; (stride * height) as usize];
+        let image = unsafe { ImageSurface::create_for_data_unsafe(pixels.as_mut_ptr(), Format::ARgb32, width, height, stride)? };
+        let context = Context::new(&image)?;
+        let overlay = unsafe { create_texture(&gl.gl, width, height, None)? };
+
+        Ok(Self { gl, surface, _window: window, width, height, pixels, context, overlay, frozen: None, invalid: true, uploads: None })
+    }
+
+    unsafe fn draw_fill(&self, rect: (f64, f64, f64, f64), radius: f64, color: [f32; 4]) {
+        let gl = &self.gl.gl;
+        gl.use_program(Some(self.gl.fill_program));
+        self.set_rect(self.gl.fill_program, rect);
+        gl.uniform_4_f32_slice(gl.get_uniform_location(self.gl.fill_program, "color").as_ref(), &color);
+        gl.uniform_1_f32(gl.get_uniform_location(self.gl.fill_program, "radius").as_ref(), radius.min(rect.2 / 2.0).min(rect.3 / 2.0).max(0.0) as f32);
+        gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+    }
+
+    unsafe fn draw_texture(&self, texture: glow::Texture) {
+        let gl = &self.gl.gl;
+        gl.use_program(Some(self.gl.texture_program));
+        self.set_rect(self.gl.texture_program, (0.0, 0.0, self.width as f64, self.height as f64));
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.uniform_1_i32(gl.get_uniform_location(self.gl.texture_program, "image").as_ref(), 0);
+        gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+    }
+
+    unsafe fn set_rect(&self, program: glow::Program, (x, y, w, h): (f64, f64, f64, f64)) {
+        let gl = &self.gl.gl;
+        gl.uniform_4_f32(gl.get_uniform_location(program, "rect").as_ref(), x as f32, y as f32, w as f32, h as f32);
+        gl.uniform_2_f32(gl.get_uniform_location(program, "size").as_ref(), self.width as f32, self.height as f32);
+    }
+
+    // Paints the frozen frame at the surface's size once, it never changes afterwards
+    fn frozen_texture(&mut self, background: &Background) -> Result<Option<glow::Texture>, Error> {
+        let Some(capture) = background.frozen else {
+            return Ok(None);
+        };
+        if self.frozen.is_none() {
+            let mut image = ImageSurface::create(Format::ARgb32, self.width, self.height)?;
+            {
+                let ctx = Context::new(&image)?;
+                ctx.scale(self.width as f64 / background.logical_size.0 as f64, self.height as f64 / background.logical_size.1 as f64);
+                if !capture.paint(&ctx, background.transform, background.logical_size, Filter::Good)? {
+                    return Ok(None);
+                }
+            }
+            let data = image.data().map_err(|e| Error::Gpu(e.to_string()))?;
+            self.frozen = Some(unsafe { create_texture(&self.gl.gl, self.width, self.height, Some(&data[..]))? });
+        }
+        Ok(self.frozen)
+    }
+}
+
+unsafe fn create_texture(gl: &glow::Context, width: i32, height: i32, pixels: Option<&[u8]>) -> Result<glow::Texture, Error> {
+    let texture = gl.create_texture().map_err(Error::Gpu)?;
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+    gl.tex_image_2d(glow::TEXTURE_2D, 0, glow::RGBA as i32, width, height, 0, glow::RGBA, glow::UNSIGNED_BYTE, pixels);
+    Ok(texture)
+}
+
+// Cairo and the compositor both expect premultiplied alpha
+fn premultiplied(color: Color) -> [f32; 4] {
+    [(color.r * color.a) as f32, (color.g * color.a) as f32, (color.b * color.a) as f32, color.a as f32]
+}
+
+impl RenderTarget for GpuSurface {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn invalid(&self) -> bool {
+        self.invalid
+    }
+
+    fn invalidate(&mut self) {
+        self.invalid = true;
+    }
+
+    fn composites_background(&self) -> bool {
+        true
+    }
+
+    // The overlay stays in memory between frames, so only what changed since the last one is redrawn
+    fn begin(&mut self, changed: &[BufferRect]) -> Result<Option<(Context, Option<Vec<BufferRect>>)>, Error> {
+        let redraw = (!self.invalid).then(|| changed.to_vec());
+        self.invalid = false;
+        self.uploads = redraw.clone();
+        Ok(Some((self.context.clone(), redraw)))
+    }
+
+    // Everything but the overlay is cheap for the GPU, so the whole surface is drawn every frame
+    fn present(&mut self, _: &WlSurface, _: &[BufferRect], _: bool, background: &Background) -> Result<(), Error> {
+        self.context.target().flush();
+        let gl_state = self.gl.clone();
+        gl_state.egl.make_current(gl_state.display, Some(self.surface), Some(self.surface), Some(gl_state.context)).map_err(gpu_error("eglMakeCurrent"))?;
+        let frozen = self.frozen_texture(background)?;
+        let gl = &gl_state.gl;
+        let row = (self.width * 4) as usize;
+
+        unsafe {
+            // Whole rows are uploaded, GLES2 can't skip pixels within a row
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.overlay));
+            let rows: Vec<(i32, i32)> = match self.uploads.take() {
+                Some(rects) => rects.iter().map(|&(_, y, _, h)| (y.clamp(0, self.height), (y + h).clamp(0, self.height))).filter(|(top, bottom)| bottom > top).collect(),
+                None => vec![(0, self.height)],
+            };
+            for (top, bottom) in rows {
+                let data = &self.pixels[top as usize * row..bottom as usize * row];
+                gl.tex_sub_image_2d(glow::TEXTURE_2D, 0, 0, top, self.width, bottom - top, glow::RGBA, glow::UNSIGNED_BYTE, glow::PixelUnpackData::Slice(data));
+            }
+
+            gl.viewport(0, 0, self.width, self.height);
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(gl_state.quad));
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 0, 0);
+            gl.disable(glow::BLEND);
+
+            // With --freeze the captured frame goes underneath and the dim layer on top of it
+            let full = (0.0, 0.0, self.width as f64, self.height as f64);
+            match frozen {
+                Some(texture) => {
+                    self.draw_texture(texture);
+                    if let Some(dim) = background.dim {
+                        gl.enable(glow::BLEND);
+                        gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+                        self.draw_fill(full, 0.0, premultiplied(dim));
+                    }
+                }
+                None => {
+                    let [r, g, b, a] = background.dim.map_or([0.0; 4], premultiplied);
+                    gl.clear_color(r, g, b, a);
+                    gl.clear(glow::COLOR_BUFFER_BIT);
+                }
+            }
+
+            // Like cairo's source operator, the fill replaces what's underneath as far as it covers it
+            gl.enable(glow::BLEND);
+            for &(rect, radius) in &background.fills {
+                gl.blend_func(glow::ZERO, glow::ONE_MINUS_SRC_ALPHA);
+                self.draw_fill(rect, radius, [1.0; 4]);
+                gl.blend_func(glow::ONE, glow::ONE);
+                self.draw_fill(rect, radius, premultiplied(background.fill_color));
+            }
+
+            gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+            self.draw_texture(self.overlay);
+        }
+
+        // Attaches, damages and commits the surface
+        gl_state.egl.swap_buffers(gl_state.display, self.surface).map_err(gpu_error("eglSwapBuffers"))
+    }
+
+    fn destroy(&mut self) {
+        let gl = &self.gl;
+        if gl.egl.make_current(gl.display, None, None, Some(gl.context)).is_ok() {
+            unsafe {
+                gl.gl.delete_texture(self.overlay);
+                if let Some(frozen) = self.frozen.take() {
+                    gl.gl.delete_texture(frozen);
+                }
+            }
+        }
+        let _ = gl.egl.destroy_surface(gl.display, self.surface);
+    }
+}
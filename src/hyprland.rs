@@ -0,0 +1,81 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::boxes::CandidateBox;
+use crate::windows::WindowProvider;
+
+pub struct Hyprland {
+    socket_path: PathBuf,
+}
+
+impl Hyprland {
+    pub fn from_env() -> Option<Self> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+        // Hyprland >= 0.40 keeps its sockets in the runtime dir, older versions used /tmp/hypr
+        let socket_path = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr").join(&signature).join(".socket.sock"))
+            .filter(|path| path.exists())
+            .unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&signature).join(".socket.sock"));
+        Some(Hyprland { socket_path })
+    }
+
+    // Each request uses its own connection, Hyprland closes it after replying
+    fn request(&self, command: &str) -> Result<Value, String> {
+        let io_error = |e: std::io::Error| format!("request '{}' failed: {}", command, e);
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(|e| format!("failed to connect to {}: {}", self.socket_path.display(), e))?;
+        stream.write_all(command.as_bytes()).map_err(io_error)?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).map_err(io_error)?;
+
+        serde_json::from_slice(&reply).map_err(|e| format!("invalid JSON: {}", e))
+    }
+}
+
+impl WindowProvider for Hyprland {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
+    // Returns the windows on the visible workspaces
+    fn windows(&self) -> Result<Vec<CandidateBox>, String> {
+        Ok(window_boxes(&self.request("j/monitors")?, &self.request("j/clients")?))
+    }
+}
+
+fn window_boxes(monitors: &Value, clients: &Value) -> Vec<CandidateBox> {
+    // Only the active (and open special) workspace of each monitor is on screen
+    let visible_workspaces: Vec<i64> = monitors
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|m| [m["activeWorkspace"]["id"].as_i64(), m["specialWorkspace"]["id"].as_i64()])
+        .flatten()
+        .filter(|&id| id != 0)
+        .collect();
+
+    let mut boxes = Vec::new();
+    for client in clients.as_array().into_iter().flatten() {
+        let visible = client["mapped"].as_bool() == Some(true)
+            && client["hidden"].as_bool() != Some(true)
+            && client["workspace"]["id"].as_i64().is_some_and(|id| visible_workspaces.contains(&id));
+        if !visible {
+            continue;
+        }
+        // Client positions and sizes are already in the global logical layout space, the
+        // same space xdg-output reports, so scaled monitors need no conversion here
+        if let (Some(x), Some(y), Some(w), Some(h)) = (client["at"][0].as_f64(), client["at"][1].as_f64(), client["size"][0].as_f64(), client["size"][1].as_f64()) {
+            if w > 0.0 && h > 0.0 {
+                boxes.push(CandidateBox {
+                    rect: (x, y, w, h),
+                    label: None,
+                    app_id: client["class"].as_str().map(str::to_string),
+                    title: client["title"].as_str().map(str::to_string),
+                });
+            }
+        }
+    }
+    boxes
+}
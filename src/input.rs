@@ -0,0 +1,514 @@
+use tracing::debug;
+use xkbcommon::xkb;
+
+use crate::boxes::{self, parse_rect, parse_size};
+use crate::geometry::{clamp_point, clamp_rect, get_selection_box, handle_at, intersect_rect, rect_contains, resize_rect, shift_into, snap_to_grid, Handle, SelectionAnchor};
+use crate::options::ButtonAction;
+use crate::render::{BUTTON_GAP, BUTTON_SIZE, HANDLE_SIZE};
+use crate::{CandidateBox, Selection, State};
+
+// A wheel notch scrolls by this many surface units in wl_pointer.axis on most compositors
+const SCROLL_UNIT: f64 = 15.0;
+const DOUBLE_CLICK_MS: u32 = 400;
+// Surface units the pointer may move between the two clicks
+const DOUBLE_CLICK_SLOP: f64 = 4.0;
+// Longest text the coordinate prompt takes, it has to fit the label drawn next to the cursor
+const PROMPT_MAX_LEN: usize = 24;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EditDrag {
+    handle: Handle,
+    // Pointer position and rectangle when the handle was grabbed
+    origin: (f64, f64),
+    rect: (f64, f64, f64, f64),
+}
+
+impl State {
+    // Fixed-size selections are centered on the pointer
+    pub(crate) fn fixed_size_rect(&self) -> Option<(f64, f64, f64, f64)> {
+        let (w, h) = self.options.fixed_size?;
+        Some((self.current_pos.0 - w / 2.0, self.current_pos.1 - h / 2.0, w, h))
+    }
+
+    // A click picks the result in these modes, no region is drawn
+    fn picks_on_click(&self) -> bool {
+        self.options.point || self.options.output || self.options.restrict || self.options.fixed_size.is_some()
+    }
+
+    // Boxes read for --snap-edges are only snap targets, a click picks one with -r and --windows
+    fn picks_boxes(&self) -> bool {
+        self.options.restrict || self.options.windows
+    }
+
+    // The box a click picks, highlighted until a selection is started
+    pub(crate) fn hovered_box(&self) -> Option<&CandidateBox> {
+        if !self.picks_boxes() || self.start_pos.is_some() || self.edit_rect.is_some() {
+            return None;
+        }
+        self.box_at(self.current_pos.0, self.current_pos.1)
+    }
+
+    pub(crate) fn follows_pointer(&self) -> bool {
+        self.start_pos.is_some() || self.edit_drag.is_some() || self.options.point || self.options.output || self.options.fixed_size.is_some()
+    }
+
+    fn add_result(&mut self, selection: (f64, f64, f64, f64), chosen: Option<CandidateBox>) {
+        debug!(rect = ?selection, label = ?chosen.as_ref().and_then(|b| b.label.as_deref()), "selection made");
+        let result = self.selection_result(selection, chosen);
+        self.results.push(result);
+    }
+
+    pub(crate) fn selection_result(&self, selection: (f64, f64, f64, f64), chosen: Option<CandidateBox>) -> Selection {
+        let output = self.containing_output(selection);
+        Selection {
+            rect: selection,
+            output_name: output.and_then(|o| o.name.clone()),
+            output_pos: output.map_or((0, 0), |o| o.logical_pos),
+            output_scale: output.map_or(1.0, |o| o.preferred_scale.unwrap_or(o.scale as f64)),
+            single_output: output.is_some_and(|o| intersect_rect(selection, o.logical_rect()) == Some(selection)),
+            chosen,
+        }
+    }
+
+    // Starts a selection at the cursor, or completes it right away in the click-to-pick modes
+    pub(crate) fn press(&mut self) {
+        if self.options.point {
+            // A single click selects a 1x1 region at the pointer
+            let (x, y) = self.current_pos;
+            self.add_result((x, y, 1.0, 1.0), None);
+            self.running = false;
+        } else if let Some(rect) = self.fixed_size_rect() {
+            self.add_result(rect, None);
+            self.running = false;
+        } else if self.options.restrict {
+            if let Some(b) = self.box_at(self.current_pos.0, self.current_pos.1).cloned() {
+                self.add_result(b.rect, Some(b));
+                self.running = false;
+            }
+        } else if self.options.output {
+            if let Some(rect) = self.current_output.and_then(|i| self.outputs.get(i)).map(|o| o.logical_rect()) {
+                self.add_result(rect, None);
+                self.running = false;
+            }
+        } else {
+            // In edit mode grabbing a handle adjusts the kept selection, dragging it from inside or with
+            // Alt moves it in edit and confirm mode, and clicking elsewhere starts over
+            if let Some(rect) = self.edit_rect {
+                if self.options.confirm {
+                    let (accept, cancel) = self.confirm_buttons(rect);
+                    if rect_contains(accept, self.current_pos) {
+                        self.add_result(rect, None);
+                        self.running = false;
+                        return;
+                    }
+                    if rect_contains(cancel, self.current_pos) {
+                        self.edit_rect = None;
+                        self.draw();
+                        return;
+                    }
+                }
+                if self.options.edit {
+                    if let Some(handle) = handle_at(rect, self.current_pos, HANDLE_SIZE) {
+                        debug!(?handle, "handle grabbed");
+                        self.edit_drag = Some(EditDrag { handle, origin: self.current_pos, rect });
+                        return;
+                    }
+                }
+                if rect_contains(rect, self.current_pos) || self.modifier_active(xkb::MOD_NAME_ALT) {
+                    debug!(rect = ?rect, "selection grabbed");
+                    self.edit_drag = Some(EditDrag { handle: Handle::Move, origin: self.current_pos, rect });
+                    return;
+                }
+                self.edit_rect = None;
+            }
+            debug!(pos = ?self.current_pos, "selection started");
+            self.start_pos = Some(self.current_pos);
+            self.end_pos = self.current_pos;
+        }
+    }
+
+    // Runs the action bound to the button with --bind
+    pub(crate) fn button(&mut self, button: u32, pressed: bool) {
+        let Some(&(_, action)) = self.options.button_bindings.iter().find(|&&(b, _)| b == button) else {
+            return;
+        };
+        match (action, pressed) {
+            (ButtonAction::StartSelection, true) => {
+                self.button_held = true;
+                self.press();
+            }
+            (ButtonAction::StartSelection, false) => {
+                self.release();
+                self.button_held = false;
+            }
+            (ButtonAction::Cancel, true) => self.running = false,
+            (ButtonAction::Confirm, true) => self.confirm(),
+            (ButtonAction::PickBox, true) => self.pick_box(),
+            (ButtonAction::Pick, true) => {
+                self.pick_box();
+                self.pick_output();
+            }
+            _ => {}
+        }
+    }
+
+    // Tracks presses of the selection button, true for the second click of a double click on a candidate box
+    // Only the modes that keep a clicked region on screen need it, elsewhere the first click already
+    // picks the box or ends the selection
+    pub(crate) fn double_click(&mut self, button: u32, time: u32) -> bool {
+        if !(self.options.multiple || self.options.edit || self.options.confirm) {
+            return false;
+        }
+        if !self.options.button_bindings.iter().any(|&(b, action)| b == button && action == ButtonAction::StartSelection) {
+            return false;
+        }
+        let pos = self.current_pos;
+        let double = self.last_click.is_some_and(|(t, p)| time.wrapping_sub(t) <= DOUBLE_CLICK_MS && (pos.0 - p.0).abs() <= DOUBLE_CLICK_SLOP && (pos.1 - p.1).abs() <= DOUBLE_CLICK_SLOP);
+        self.last_click = if double { None } else { Some((time, pos)) };
+        double && self.box_at(pos.0, pos.1).is_some()
+    }
+
+    // The box is taken like a dragged region, it joins the collected regions or stays on screen for confirming
+    pub(crate) fn select_hovered_box(&mut self) {
+        self.start_pos = None;
+        self.edit_drag = None;
+        if let Some(rect) = self.box_at(self.current_pos.0, self.current_pos.1).map(|b| b.rect) {
+            debug!(?rect, "box selected by double click");
+            self.finish_selection(rect);
+        }
+    }
+
+    fn pick_box(&mut self) {
+        if let Some(b) = self.box_at(self.current_pos.0, self.current_pos.1).cloned() {
+            self.add_result(b.rect, Some(b));
+            self.running = false;
+        }
+    }
+
+    fn pick_output(&mut self) {
+        if let (true, Some(index)) = (self.running, self.output_index_at(self.current_pos.0, self.current_pos.1)) {
+            self.add_result(self.outputs[index].logical_rect(), None);
+            self.running = false;
+        }
+    }
+
+    fn snap(&self, pos: (f64, f64)) -> (f64, f64) {
+        let pos = snap_to_grid(pos, self.options.snap);
+        match self.options.snap_edges {
+            Some(threshold) => boxes::snap_to_edges(pos, &self.boxes, threshold),
+            None => pos,
+        }
+    }
+
+    // Holding Ctrl expands the selection around the press point, holding Shift while dragging
+    // with the pointer keeps it square (Shift+arrows are reserved for keyboard resizing)
+    pub(crate) fn current_selection(&self) -> Option<(f64, f64, f64, f64)> {
+        let start = self.snap(self.start_pos?);
+        let end = self.snap(self.end_pos);
+        let anchor = if self.modifier_active(xkb::MOD_NAME_CTRL) { SelectionAnchor::Center } else { SelectionAnchor::Corner };
+        let aspect_ratio = if self.button_held && self.modifier_active(xkb::MOD_NAME_SHIFT) { Some(1.0) } else { self.options.aspect_ratio };
+        let selection = self.clamp_selection(get_selection_box(start, end, aspect_ratio, anchor));
+        Some(self.clamp_to_start_output(selection, self.start_pos?))
+    }
+
+    // A moved selection keeps its size and stops at the edge of the layout, or of its output with --single-output
+    fn keep_on_outputs(&self, rect: (f64, f64, f64, f64), start: (f64, f64)) -> (f64, f64, f64, f64) {
+        let bounds = match self.output_at(start.0, start.1) {
+            Some(output) if self.options.single_output => Some(output.logical_rect()),
+            _ => self.outputs_bounds(),
+        };
+        bounds.map_or(rect, |bounds| shift_into(rect, bounds))
+    }
+
+    // Nothing outside of the outputs is ever reported, clicks and other empty selections keep their position
+    fn clamp_selection(&self, rect: (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        match (self.clamp_to_outputs(rect), self.outputs_bounds()) {
+            (Some(clamped), _) => clamped,
+            (None, Some(bounds)) => clamp_rect(rect, bounds),
+            (None, None) => rect,
+        }
+    }
+
+    // With --single-output a selection can't leave the output it was started on
+    fn clamp_to_start_output(&self, rect: (f64, f64, f64, f64), start: (f64, f64)) -> (f64, f64, f64, f64) {
+        match self.output_at(start.0, start.1) {
+            Some(output) if self.options.single_output => clamp_rect(rect, output.logical_rect()),
+            _ => rect,
+        }
+    }
+
+    // Regions collapsed to a line, or below --min-size when it's set
+    fn too_small(&self, (_, _, w, h): (f64, f64, f64, f64)) -> bool {
+        w <= 1.0 || h <= 1.0 || self.options.min_size.is_some_and(|(min_w, min_h)| w < min_w || h < min_h)
+    }
+
+    // A click still picks a box or cancels, a drag that's too small is rejected and the user can start over
+    pub(crate) fn below_min_size(&self, (x, y, w, h): (f64, f64, f64, f64)) -> bool {
+        let is_click = w <= 1.0 && h <= 1.0;
+        !is_click && self.too_small((x, y, w, h))
+    }
+
+    pub(crate) fn release(&mut self) {
+        if let Some(drag) = self.edit_drag.take() {
+            debug!(handle = ?drag.handle, rect = ?self.edit_rect, "handle released");
+            self.draw();
+            return;
+        }
+        if let Some(selection) = self.current_selection() {
+            self.start_pos = None;
+            self.finish_selection(selection);
+        }
+    }
+
+    // Takes a dragged or typed region the way the selection mode asks for
+    fn finish_selection(&mut self, selection: (f64, f64, f64, f64)) {
+        // The rejected drag disappears and the user can start over
+        if self.below_min_size(selection) {
+            debug!(rect = ?selection, "selection below the minimum size");
+            self.draw();
+            return;
+        }
+        // In multi-region mode regions are collected until the user confirms with Enter
+        if self.options.multiple {
+            if selection.2 > 1.0 && selection.3 > 1.0 {
+                self.selections.push(selection);
+            }
+            self.draw();
+            return;
+        }
+        // In edit and confirm mode the selection stays on screen until it's confirmed with Enter
+        if self.options.edit || self.options.confirm {
+            if selection.2 > 1.0 && selection.3 > 1.0 {
+                self.edit_rect = Some(selection);
+            }
+            self.draw();
+            return;
+        }
+        // A click without dragging picks the box under the cursor in the box picking modes, otherwise
+        // it counts as cancellation
+        if selection.2 > 1.0 && selection.3 > 1.0 {
+            self.add_result(selection, None);
+        } else if let Some(b) = self.box_at(self.current_pos.0, self.current_pos.1).filter(|_| self.picks_boxes()).cloned() {
+            self.add_result(b.rect, Some(b));
+        }
+        self.running = false;
+    }
+
+    // Finalizes the in-progress selection, or picks what is under the cursor in the click-to-pick modes
+    pub(crate) fn confirm(&mut self) {
+        if let Some(rect) = self.edit_rect {
+            self.add_result(rect, None);
+            self.running = false;
+        } else if self.start_pos.is_some() {
+            self.release();
+        } else if self.options.multiple {
+            for selection in self.selections.clone() {
+                self.add_result(selection, None);
+            }
+            self.running = false;
+        } else if self.picks_on_click() {
+            self.press();
+        }
+    }
+
+    // Accept and Cancel sit below the bottom right corner, or just inside it at the bottom of an output
+    pub(crate) fn confirm_buttons(&self, rect: (f64, f64, f64, f64)) -> ((f64, f64, f64, f64), (f64, f64, f64, f64)) {
+        let (x, y, w, h) = rect;
+        let right = x + w;
+        let below = y + h + BUTTON_GAP;
+        let top = if self.output_at(right - 1.0, below + BUTTON_SIZE).is_some() { below } else { y + h - BUTTON_GAP - BUTTON_SIZE };
+        let accept = (right - 2.0 * BUTTON_SIZE - BUTTON_GAP, top, BUTTON_SIZE, BUTTON_SIZE);
+        let cancel = (right - BUTTON_SIZE, top, BUTTON_SIZE, BUTTON_SIZE);
+        (accept, cancel)
+    }
+
+    fn undo(&mut self) {
+        if self.selections.pop().is_some() {
+            self.draw();
+        }
+    }
+
+    // Moves the virtual cursor in logical coordinates, crossing into neighbouring outputs
+    // and otherwise stopping at the edge of the current one
+    fn move_cursor(&mut self, dx: f64, dy: f64) {
+        let (mut x, mut y) = (self.current_pos.0 + dx, self.current_pos.1 + dy);
+        let index = match self.output_index_at(x, y) {
+            Some(index) => index,
+            None => {
+                if self.outputs.is_empty() {
+                    return;
+                }
+                let index = self.current_output.unwrap_or(self.focused_output).min(self.outputs.len() - 1);
+                (x, y) = clamp_point((x, y), self.outputs[index].logical_rect());
+                index
+            }
+        };
+        self.set_cursor((x, y));
+        self.current_output = Some(index);
+        self.focused_output = index;
+        self.draw();
+    }
+
+    // Moves the virtual cursor to the center of the next output
+    fn focus_next_output(&mut self) {
+        if self.outputs.is_empty() {
+            return;
+        }
+        let index = (self.focused_output + 1) % self.outputs.len();
+        let (x, y, w, h) = self.outputs[index].logical_rect();
+        self.set_cursor((x + w / 2.0, y + h / 2.0));
+        self.current_output = Some(index);
+        self.focused_output = index;
+        self.draw();
+    }
+
+    pub(crate) fn set_cursor(&mut self, pos: (f64, f64)) {
+        if let Some(pointer_move) = self.callbacks.pointer_move.as_mut() {
+            pointer_move(pos);
+        }
+        // The free corner keeps its offset from the pointer after it was nudged with the keyboard or the wheel
+        if self.start_pos.is_some() {
+            self.end_pos = (self.end_pos.0 + pos.0 - self.current_pos.0, self.end_pos.1 + pos.1 - self.current_pos.1);
+        } else {
+            self.end_pos = pos;
+        }
+        self.current_pos = pos;
+        if let Some(drag) = self.edit_drag {
+            let (dx, dy) = (pos.0 - drag.origin.0, pos.1 - drag.origin.1);
+            let rect = resize_rect(drag.rect, drag.handle, dx, dy);
+            let rect = match drag.handle {
+                Handle::Move => self.keep_on_outputs(rect, (drag.rect.0, drag.rect.1)),
+                _ => self.clamp_to_start_output(self.clamp_selection(rect), (drag.rect.0, drag.rect.1)),
+            };
+            // Handles stop where the kept selection would get too small
+            if drag.handle == Handle::Move || !self.too_small(rect) {
+                self.edit_rect = Some(rect);
+            }
+        }
+    }
+
+    fn resize_selection(&mut self, dx: f64, dy: f64) {
+        if self.start_pos.is_some() {
+            self.end_pos = (self.end_pos.0 + dx, self.end_pos.1 + dy);
+            self.draw();
+        }
+    }
+
+    // Scrolling up grows the selection around its center and down shrinks it, horizontal scrolling
+    // and Shift only change the width, Alt only the height
+    pub(crate) fn scroll(&mut self, horizontal: bool, value: f64) {
+        let amount = -value / SCROLL_UNIT * self.options.scroll_step / 2.0;
+        let (dx, dy) = if horizontal || self.modifier_active(xkb::MOD_NAME_SHIFT) {
+            (amount, 0.0)
+        } else if self.modifier_active(xkb::MOD_NAME_ALT) {
+            (0.0, amount)
+        } else {
+            (amount, amount)
+        };
+        if let Some(start) = self.start_pos {
+            let end = self.end_pos;
+            let (sx, sy) = ((end.0 - start.0).signum(), (end.1 - start.1).signum());
+            // Shrinking stops at a point, the edges never cross
+            let (dx, dy) = (dx.max(-(end.0 - start.0).abs() / 2.0), dy.max(-(end.1 - start.1).abs() / 2.0));
+            if self.modifier_active(xkb::MOD_NAME_CTRL) {
+                // The press point is already the center
+                self.end_pos = (end.0 + sx * dx, end.1 + sy * dy);
+            } else {
+                self.start_pos = Some((start.0 - sx * dx, start.1 - sy * dy));
+                self.end_pos = (end.0 + sx * dx, end.1 + sy * dy);
+            }
+        } else if let Some((x, y, w, h)) = self.edit_rect {
+            let (new_w, new_h) = ((w + 2.0 * dx).max(1.0), (h + 2.0 * dy).max(1.0));
+            let rect = self.clamp_to_start_output(self.clamp_selection((x + (w - new_w) / 2.0, y + (h - new_h) / 2.0, new_w, new_h)), (x, y));
+            if self.too_small(rect) {
+                return;
+            }
+            self.edit_rect = Some(rect);
+        } else {
+            return;
+        }
+        self.draw();
+    }
+
+    fn modifier_active(&self, name: &str) -> bool {
+        self.seat.xkb_state.as_ref().is_some_and(|s| s.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE))
+    }
+
+    pub(crate) fn handle_key(&mut self, keysym: xkb::Keysym) {
+        if self.prompt.is_some() {
+            self.prompt_key(keysym);
+            return;
+        }
+        let step = self.options.step;
+        let shift = self.modifier_active(xkb::MOD_NAME_SHIFT);
+        match keysym {
+            xkb::Keysym::Escape => {
+                self.running = false;
+            }
+            // Shift+arrows move the free corner of the selection, leaving the cursor in place
+            xkb::Keysym::Left if shift => self.resize_selection(-step, 0.0),
+            xkb::Keysym::Right if shift => self.resize_selection(step, 0.0),
+            xkb::Keysym::Up if shift => self.resize_selection(0.0, -step),
+            xkb::Keysym::Down if shift => self.resize_selection(0.0, step),
+            xkb::Keysym::Left => self.move_cursor(-step, 0.0),
+            xkb::Keysym::Right => self.move_cursor(step, 0.0),
+            xkb::Keysym::Up => self.move_cursor(0.0, -step),
+            xkb::Keysym::Down => self.move_cursor(0.0, step),
+            xkb::Keysym::Return | xkb::Keysym::KP_Enter => self.confirm(),
+            xkb::Keysym::Tab => self.focus_next_output(),
+            xkb::Keysym::colon if !self.picks_on_click() => {
+                self.prompt = Some(String::new());
+                self.draw();
+            }
+            xkb::Keysym::BackSpace => self.undo(),
+            xkb::Keysym::z if self.modifier_active(xkb::MOD_NAME_CTRL) => self.undo(),
+            // Space starts the selection at the virtual cursor and finishes it on the second press
+            xkb::Keysym::space => {
+                if self.start_pos.is_some() {
+                    self.release();
+                } else {
+                    self.press();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Line editing in the coordinate prompt, Escape closes it and Enter takes the typed region
+    fn prompt_key(&mut self, keysym: xkb::Keysym) {
+        let Some(text) = self.prompt.as_mut() else {
+            return;
+        };
+        match keysym {
+            xkb::Keysym::Escape => self.prompt = None,
+            xkb::Keysym::Return | xkb::Keysym::KP_Enter => self.submit_prompt(),
+            xkb::Keysym::BackSpace => {
+                text.pop();
+            }
+            _ => match char::from_u32(xkb::keysym_to_utf32(keysym)) {
+                Some(c) if !c.is_control() && text.len() < PROMPT_MAX_LEN => text.push(c),
+                _ => return,
+            },
+        }
+        self.draw();
+    }
+
+    // 'x,y WxH' is taken as is and 'WxH' starts at the cursor, invalid text stays in the prompt to be fixed
+    fn submit_prompt(&mut self) {
+        let Some(text) = self.prompt.take() else {
+            return;
+        };
+        let rect = if text.contains(',') { parse_rect(&text) } else { parse_size(&text).map(|(w, h)| (self.current_pos.0, self.current_pos.1, w, h)) };
+        // The top left corner stands in for the start of a drag with --single-output
+        let rect = rect.ok().and_then(|rect| self.clamp_to_outputs(rect)).map(|rect| self.clamp_to_start_output(rect, (rect.0, rect.1)));
+        let Some(rect) = rect.filter(|&rect| !self.too_small(rect)) else {
+            debug!(text, "invalid region typed");
+            self.prompt = Some(text);
+            return;
+        };
+        debug!(?rect, "region typed");
+        self.start_pos = None;
+        self.edit_drag = None;
+        self.finish_selection(rect);
+    }
+}
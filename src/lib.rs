@@ -0,0 +1,479 @@
+pub mod boxes;
+#[cfg(feature = "tokio")]
+mod async_select;
+mod builder;
+mod callbacks;
+pub mod cli;
+pub mod clipboard;
+pub mod color;
+#[cfg(feature = "control")]
+pub mod control;
+pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod error;
+mod event_loop;
+pub mod format;
+mod geometry;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "hyprland")]
+mod hyprland;
+mod input;
+#[cfg(feature = "niri")]
+mod niri;
+mod options;
+mod render;
+mod screencopy;
+mod shm;
+mod signals;
+#[cfg(feature = "sway")]
+mod sway;
+mod text;
+mod wayland;
+pub mod windows;
+
+use std::fs::File;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use cairo::{Context, Format, ImageSurface};
+
+use wayland_client::globals::registry_queue_init;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_client::{Connection, EventQueue, QueueHandle};
+
+use smithay_client_toolkit::output::OutputState as OutputRegistry;
+
+pub use boxes::CandidateBox;
+pub use builder::SelectionOptions;
+pub use callbacks::Callbacks;
+#[cfg(feature = "tokio")]
+pub use async_select::{select_async, select_with_callbacks_async};
+pub use error::Error;
+use format::{FormatContext, FormatOptions};
+use geometry::{bounding_rect, intersect_rect, rect_contains, round_rect};
+use input::EditDrag;
+pub use options::{ButtonAction, Options};
+use options::RenderBackend;
+use render::Renderer;
+use wayland::{Globals, OutputState, Seat};
+
+// Exit codes of the binary, documented in --help
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_CANCELLED: i32 = 1;
+pub const EXIT_UNSUPPORTED: i32 = 2;
+pub const EXIT_ERROR: i32 = 3;
+pub const EXIT_TIMEOUT: i32 = 4;
+
+pub type Rect = (f64, f64, f64, f64);
+
+// A confirmed region along with what it was picked from
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub rect: Rect,
+    pub output_name: Option<String>,
+    pub output_pos: (i32, i32),
+    pub output_scale: f64,
+    // Whether the region lies entirely on the output above, which screen recorders need
+    pub single_output: bool,
+    pub chosen: Option<CandidateBox>,
+}
+
+impl Selection {
+    // Formats the selection like the command line does, None picks the default format
+    pub fn format(&self, format: Option<&str>) -> String {
+        self.format_with(format, FormatOptions::default())
+    }
+
+    // Like format, with the command line's --float and --pixels
+    pub fn format_with(&self, format: Option<&str>, options: FormatOptions) -> String {
+        let ctx = self.format_context();
+        let default_format = if ctx.label.is_some() { format::DEFAULT_LABELED_FORMAT } else { format::DEFAULT_FORMAT };
+        format::expand_with(&format::parse(format.unwrap_or(default_format)), &ctx, self.rect, options)
+    }
+
+    // The variables --exec hands to its command
+    pub fn shell_variables(&self, options: FormatOptions) -> Vec<(&'static str, String)> {
+        format::shell_variables_with(&self.format_context(), self.rect, options)
+    }
+
+    // The geometry of the context is rounded, format_with prints it unrounded
+    pub fn format_context(&self) -> FormatContext<'_> {
+        let (x, y, width, height) = round_rect(self.rect);
+        FormatContext {
+            x: x as i32,
+            y: y as i32,
+            width: width as i32,
+            height: height as i32,
+            output_x: self.output_pos.0,
+            output_y: self.output_pos.1,
+            output_scale: self.output_scale,
+            output_name: self.output_name.as_deref(),
+            label: self.chosen.as_ref().and_then(|b| b.label.as_deref()),
+            app_id: self.chosen.as_ref().and_then(|b| b.app_id.as_deref()),
+            title: self.chosen.as_ref().and_then(|b| b.title.as_deref()),
+        }
+    }
+}
+
+// Lets the user select a region, None when the selection was cancelled
+pub fn select(options: impl Into<Options>) -> Result<Option<Rect>, Error> {
+    Ok(select_all(options.into(), Vec::new())?.into_iter().next().map(|s| s.rect))
+}
+
+// Runs a selection with the given candidate boxes, returns every confirmed region (several with
+// `multiple`) or nothing when it was cancelled
+pub fn select_all(options: Options, boxes: Vec<CandidateBox>) -> Result<Vec<Selection>, Error> {
+    select_with_callbacks(options, boxes, Callbacks::default())
+}
+
+pub fn select_with_callbacks(options: Options, boxes: Vec<CandidateBox>, callbacks: Callbacks) -> Result<Vec<Selection>, Error> {
+    let (_conn, mut event_queue, mut state) = setup(options, boxes, callbacks, true)?;
+    let signals = if state.options.handle_signals { Some(signals::Signals::register()?) } else { None };
+    let deadline = state.options.timeout.map(|timeout| Instant::now() + timeout);
+    while state.running {
+        event_loop::dispatch(&mut event_queue, &mut state, signals.as_ref(), deadline, &[])?;
+    }
+    state.take_results()
+}
+
+fn detect_windows(boxes: &mut Vec<CandidateBox>) {
+    match windows::detect() {
+        Some(mut provider) => match provider.refresh().and_then(|_| provider.windows()) {
+            Ok(windows) => boxes.extend(windows),
+            Err(e) => eprintln!("Warning: {} IPC: {}", provider.name(), e),
+        },
+        None => eprintln!("Warning: no supported compositor IPC found, --windows has no effect"),
+    }
+}
+
+// --display takes precedence over WAYLAND_DISPLAY, names are looked up in XDG_RUNTIME_DIR like libwayland does
+pub(crate) fn connect(display: Option<&str>) -> Result<Connection, Error> {
+    let Some(display) = display else {
+        return Ok(Connection::connect_to_env()?);
+    };
+    let path = if Path::new(display).is_absolute() {
+        PathBuf::from(display)
+    } else {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").ok_or(wayland_client::ConnectError::NoCompositor)?;
+        Path::new(&runtime_dir).join(display)
+    };
+    let stream = UnixStream::connect(&path).map_err(|source| Error::Display { path, source })?;
+    Ok(Connection::from_socket(stream)?)
+}
+
+// Connects and creates the overlay surfaces, ready for the event loop. Hidden surfaces aren't
+// mapped until State::show, the daemon keeps them around between selections.
+fn setup(options: Options, mut boxes: Vec<CandidateBox>, callbacks: Callbacks, show: bool) -> Result<(Connection, EventQueue<State>, State), Error> {
+    if options.windows {
+        detect_windows(&mut boxes);
+    }
+
+    let conn = connect(options.display.as_deref())?;
+    let (global_list, mut event_queue) = registry_queue_init::<State>(&conn)?;
+    let qh = event_queue.handle();
+    let globals = Globals::bind(&global_list, &qh)?;
+    if options.inhibit_shortcuts && globals.shortcuts_inhibit_manager.is_none() {
+        eprintln!("Warning: --inhibit-shortcuts needs zwp_keyboard_shortcuts_inhibit_manager_v1, continuing without it");
+    }
+
+    let renderer: Box<dyn Renderer> = match options.renderer {
+        RenderBackend::Cpu => Box::new(render::ShmRenderer::new(globals.shm.wl_shm().clone())),
+        #[cfg(feature = "gpu")]
+        RenderBackend::Gpu => match gpu::GpuRenderer::new(&conn) {
+            Ok(renderer) => Box::new(renderer),
+            Err(e) => {
+                eprintln!("Warning: {}, drawing with the CPU instead", e);
+                Box::new(render::ShmRenderer::new(globals.shm.wl_shm().clone()))
+            }
+        },
+        #[cfg(not(feature = "gpu"))]
+        RenderBackend::Gpu => {
+            eprintln!("Warning: --renderer gpu needs rust-slurp built with the gpu feature, drawing with the CPU instead");
+            Box::new(render::ShmRenderer::new(globals.shm.wl_shm().clone()))
+        }
+    };
+
+    let mut state = State {
+        options,
+        running: true,
+        results: Vec::new(),
+        callbacks,
+        reported_selection: None,
+        error: None,
+        hidden: !show,
+        qh: qh.clone(),
+        globals,
+        output_registry: OutputRegistry::new(&global_list, &qh),
+        seat: Seat::new(&global_list, &qh),
+        renderer,
+        setup_done: false,
+        touch_point: None,
+        tool_output: None,
+        captures: Vec::new(),
+        outputs: Vec::new(),
+        pending_outputs: Vec::new(),
+        start_pos: None,
+        current_pos: (0.0, 0.0),
+        end_pos: (0.0, 0.0),
+        button_held: false,
+        last_click: None,
+        edit_rect: None,
+        edit_drag: None,
+        prompt: None,
+        current_output: None,
+        focused_output: 0,
+        selections: Vec::new(),
+        boxes,
+    };
+
+    // Roundtrip to get the outputs' info and the seat's capabilities
+    event_queue.roundtrip(&mut state)?;
+
+    // Capture before any overlay surface exists so it doesn't end up in the frozen frames. Every output the
+    // registry announced is captured, whether its surface setup is deferred or not.
+    if show {
+        let outputs: Vec<_> = state.output_registry.outputs().filter_map(|o| state.output_registry.info(&o).map(|info| (info.id, o))).collect();
+        capture_outputs(&mut event_queue, &mut state, &outputs)?;
+    }
+
+    state.setup_done = true;
+    for (name, output) in std::mem::take(&mut state.pending_outputs) {
+        state.add_output(name, output);
+    }
+    if state.outputs.is_empty() && !state.options.outputs.is_empty() {
+        return Err(Error::UnknownOutputs(state.options.outputs.join(",")));
+    }
+
+    // Second roundtrip to get the surfaces configured
+    event_queue.roundtrip(&mut state)?;
+
+    state.apply_initial();
+    Ok((conn, event_queue, state))
+}
+
+// --capture saves the screen as it was when the selection started, so it's taken along with the frozen frames
+fn capture_outputs(event_queue: &mut EventQueue<State>, state: &mut State, outputs: &[(u32, WlOutput)]) -> Result<(), Error> {
+    if state.options.capture.is_some() && state.globals.screencopy_manager.is_none() {
+        return Err(Error::MissingProtocols("zwlr_screencopy_manager_v1".to_string()));
+    }
+    if !(state.options.freeze || state.options.magnifier.is_some() || state.options.capture.is_some()) {
+        return Ok(());
+    }
+    match state.globals.screencopy_manager.clone() {
+        Some(manager) => {
+            for (name, output) in outputs {
+                state.captures.push(screencopy::Capture::new(&manager, output, *name, &state.qh));
+            }
+            while state.captures.iter().any(|c| !c.done) {
+                event_queue.blocking_dispatch(state)?;
+            }
+        }
+        None => eprintln!("Warning: --freeze and --magnifier need zwlr_screencopy_manager_v1, continuing without them"),
+    }
+    Ok(())
+}
+
+struct State {
+    options: Options,
+    running: bool,
+    // Confirmed selections, empty when cancelled
+    results: Vec<Selection>,
+    callbacks: Callbacks,
+    // Last region passed to the selection changed callback
+    reported_selection: Option<Rect>,
+    // Set when a handler fails, stops the event loop
+    error: Option<Error>,
+    // Overlay surfaces are unmapped, nothing is drawn until they're shown again
+    hidden: bool,
+    qh: QueueHandle<Self>,
+    globals: Globals,
+    // Outputs with their info from wl_output and xdg-output
+    output_registry: OutputRegistry,
+    seat: Seat,
+    renderer: Box<dyn Renderer>,
+    // Outputs announced before this are set up once the frozen frames were captured
+    setup_done: bool,
+    // Touch id and output of the finger driving the selection
+    touch_point: Option<(i32, usize)>,
+    // Output the tablet tool is in proximity of
+    tool_output: Option<usize>,
+    // Frozen frames for --freeze, keyed by the output's registry name
+    captures: Vec<screencopy::Capture>,
+    outputs: Vec<OutputState>,
+    pending_outputs: Vec<(u32, WlOutput)>,
+    start_pos: Option<(f64, f64)>,
+    current_pos: (f64, f64),
+    // Free corner of the in-progress selection, follows the cursor but can be nudged on its own
+    end_pos: (f64, f64),
+    button_held: bool,
+    // Time and position of the last press of the selection button, for double clicks
+    last_click: Option<(u32, (f64, f64))>,
+    // Finished selection kept on screen for adjustment in edit mode
+    edit_rect: Option<(f64, f64, f64, f64)>,
+    edit_drag: Option<EditDrag>,
+    // Text typed into the coordinate prompt opened with ':'
+    prompt: Option<String>,
+    current_output: Option<usize>,
+    // Output with the keyboard focus highlight, kept when the pointer leaves
+    focused_output: usize,
+    selections: Vec<(f64, f64, f64, f64)>,
+    boxes: Vec<CandidateBox>,
+}
+
+impl State {
+    fn output_index_at(&self, x: f64, y: f64) -> Option<usize> {
+        self.outputs.iter().position(|o| rect_contains(o.logical_rect(), (x, y)))
+    }
+
+    fn output_at(&self, x: f64, y: f64) -> Option<&OutputState> {
+        self.output_index_at(x, y).map(|i| &self.outputs[i])
+    }
+
+    fn outputs_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        bounding_rect(self.outputs.iter().map(|o| o.logical_rect()))
+    }
+
+    // The output holding the largest part of the rect, or the one under its corner when it has no area
+    fn containing_output(&self, rect: (f64, f64, f64, f64)) -> Option<&OutputState> {
+        let area = |o: &OutputState| intersect_rect(rect, o.logical_rect()).map_or(0.0, |(_, _, w, h)| w * h);
+        let output = self.outputs.iter().map(|o| (area(o), o)).filter(|&(a, _)| a > 0.0).max_by(|a, b| a.0.total_cmp(&b.0)).map(|(_, o)| o);
+        output.or_else(|| self.output_at(rect.0, rect.1))
+    }
+
+    // Trims the rectangle to the extent of its parts lying on outputs, None when it misses them all
+    fn clamp_to_outputs(&self, rect: (f64, f64, f64, f64)) -> Option<(f64, f64, f64, f64)> {
+        bounding_rect(self.outputs.iter().filter_map(|o| intersect_rect(rect, o.logical_rect())))
+    }
+
+    fn box_at(&self, x: f64, y: f64) -> Option<&CandidateBox> {
+        self.boxes.iter().find(|b| b.contains(x, y))
+    }
+
+    fn apply_initial(&mut self) {
+        if let Some(rect) = self.options.initial {
+            self.edit_rect = self.clamp_to_outputs(rect);
+            if self.edit_rect.is_none() {
+                eprintln!("Warning: initial selection is outside of all outputs");
+            }
+            self.draw();
+        }
+    }
+
+    // Clears what the previous selection left behind, the daemon reuses one State for every request.
+    // Surfaces, buffers and outputs are kept, which is the point of it.
+    fn reset(&mut self, options: Options, boxes: Vec<CandidateBox>) {
+        self.options = options;
+        self.boxes = boxes;
+        self.running = true;
+        self.results.clear();
+        self.reported_selection = None;
+        self.error = None;
+        self.captures.clear();
+        self.start_pos = None;
+        self.button_held = false;
+        self.last_click = None;
+        self.touch_point = None;
+        self.edit_rect = None;
+        self.edit_drag = None;
+        self.prompt = None;
+        self.selections.clear();
+        self.apply_initial();
+    }
+
+    // Committing without a buffer makes the compositor configure the surfaces again, which draws them
+    fn show(&mut self) {
+        self.hidden = false;
+        for output_state in &mut self.outputs {
+            output_state.invalidate();
+            output_state.surface.commit();
+        }
+    }
+
+    // Attaching no buffer unmaps the surfaces but keeps them and their role for the next show
+    fn hide(&mut self) {
+        self.hidden = true;
+        for output_state in &mut self.outputs {
+            // Unmapped surfaces may never get their frame callbacks
+            output_state.configured = false;
+            output_state.frame_pending = false;
+            output_state.surface.attach(None, 0, 0);
+            output_state.surface.commit();
+            if let Some(inhibitor) = output_state.shortcuts_inhibitor.take() {
+                inhibitor.destroy();
+            }
+        }
+    }
+
+    fn take_results(&mut self) -> Result<Vec<Selection>, Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if let (false, Some(path)) = (self.results.is_empty(), self.options.capture.clone()) {
+            self.save_captures(&path)?;
+        }
+        if self.results.is_empty() {
+            if let Some(cancelled) = self.callbacks.cancelled.as_mut() {
+                cancelled();
+            }
+        } else if let Some(confirmed) = self.callbacks.confirmed.as_mut() {
+            confirmed(&self.results);
+        }
+        Ok(std::mem::take(&mut self.results))
+    }
+
+    // Handlers can't return errors, the first one is kept and ends the event loop
+    fn fail(&mut self, error: Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+        self.running = false;
+    }
+
+    // Stitches the region together from the frames captured at startup, at the highest scale of the outputs it covers
+    fn capture_region(&self, rect: Rect) -> Result<ImageSurface, cairo::Error> {
+        let covered: Vec<&OutputState> = self.outputs.iter().filter(|o| intersect_rect(rect, o.logical_rect()).is_some()).collect();
+        let scale = covered.iter().map(|o| o.preferred_scale.unwrap_or(o.scale as f64)).fold(1.0, f64::max);
+        let image = ImageSurface::create(Format::ARgb32, (rect.2 * scale).round() as i32, (rect.3 * scale).round() as i32)?;
+        let ctx = Context::new(&image)?;
+        ctx.scale(scale, scale);
+        for output in covered {
+            let Some(capture) = self.captures.iter().find(|c| c.global_name == output.global_name) else {
+                continue;
+            };
+            let (x, y, width, height) = output.logical_rect();
+            ctx.save()?;
+            ctx.translate(x - rect.0, y - rect.1);
+            ctx.rectangle(0.0, 0.0, width, height);
+            ctx.clip();
+            capture.paint(&ctx, output.transform, output.effective_logical_size(), cairo::Filter::Good)?;
+            ctx.restore()?;
+        }
+        drop(ctx);
+        Ok(image)
+    }
+
+    // With several selections the later ones are numbered, shot.png, shot-2.png and so on
+    fn save_captures(&self, path: &Path) -> Result<(), Error> {
+        for (i, result) in self.results.iter().enumerate() {
+            let path = if i == 0 { path.to_path_buf() } else { numbered_path(path, i + 1) };
+            let image = self.capture_region(result.rect)?;
+            File::create(&path)
+                .map_err(cairo::IoError::Io)
+                .and_then(|mut file| image.write_to_png(&mut file))
+                .map_err(|source| Error::SaveCapture { path, source })?;
+        }
+        Ok(())
+    }
+}
+
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, n, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
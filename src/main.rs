@@ -1,421 +1,238 @@
-use std::process::exit;
-use std::os::unix::io::{AsRawFd, BorrowedFd};
-
-use cairo::{Context, Format, ImageSurface};
-use memmap2::MmapMut;
-
-use wayland_client::protocol::{
-    wl_compositor,
-    wl_keyboard,
-    wl_output::{self, WlOutput},
-    wl_pointer::{self, WlPointer},
-    wl_registry,
-    wl_seat::{self, WlSeat},
-    wl_shm::{self, WlShm},
-    wl_shm_pool::{self, WlShmPool},
-    wl_surface,
-};
-use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum};
-
-use wayland_protocols_wlr::layer_shell::v1::client::{
-    zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
-    zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
-};
-
-use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
-
-fn main() {
-    let conn = Connection::connect_to_env().unwrap();
-    let mut event_queue = conn.new_event_queue();
-    let qh = event_queue.handle();
-
-    let display = conn.display();
-    display.get_registry(&qh, ());
-
-    let mut state = State {
-        running: true,
-        exit_code: 0,
-        qh: qh.clone(),
-        compositor: None,
-        shm: None,
-        layer_shell: None,
-        xdg_output_manager: None,
-        seat: None,
-        pointer: None,
-        keyboard: None,
-        outputs: Vec::new(),
-        start_pos: None,
-        current_pos: (0.0, 0.0),
-        current_output: None,
-        selections: Vec::new(),
-    };
-
-    // First roundtrip to get globals
-    event_queue.roundtrip(&mut state).unwrap();
-
-    if state.compositor.is_none() || state.shm.is_none() || state.layer_shell.is_none() || state.seat.is_none() || state.xdg_output_manager.is_none() {
-        eprintln!("Error: Your compositor does not support the required Wayland protocols.");
-        eprintln!("Missing: {} {} {} {} {}",
-            if state.compositor.is_none() { "wl_compositor" } else { "" },
-            if state.shm.is_none() { "wl_shm" } else { "" },
-            if state.layer_shell.is_none() { "zwlr_layer_shell_v1" } else { "" },
-            if state.seat.is_none() { "wl_seat" } else { "" },
-            if state.xdg_output_manager.is_none() { "zxdg_output_manager_v1" } else { "" }
-        );
-        exit(1);
-    }
-
-    // Second roundtrip to get output info
-    event_queue.roundtrip(&mut state).unwrap();
-
-    while state.running {
-        event_queue.blocking_dispatch(&mut state).unwrap();
-    }
-
-    exit(state.exit_code);
-}
-
-struct State {
-    running: bool,
-    exit_code: i32,
-    qh: QueueHandle<Self>,
-    compositor: Option<wl_compositor::WlCompositor>,
-    shm: Option<WlShm>,
-    layer_shell: Option<ZwlrLayerShellV1>,
-    xdg_output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
-    seat: Option<WlSeat>,
-    pointer: Option<WlPointer>,
-    keyboard: Option<wl_keyboard::WlKeyboard>,
-    outputs: Vec<OutputState>,
-    start_pos: Option<(f64, f64)>,
-    current_pos: (f64, f64),
-    current_output: Option<usize>,
-    selections: Vec<(f64, f64, f64, f64)>,
-}
-
-struct OutputState {
-    output: WlOutput,
-    xdg_output: zxdg_output_v1::ZxdgOutputV1,
-    logical_pos: (i32, i32),
-    size: (u32, u32),
-    surface: wl_surface::WlSurface,
-    layer_surface: ZwlrLayerSurfaceV1,
-    buffer: Option<Buffer>,
-}
-
-struct Buffer {
-    pool: WlShmPool,
-    width: i32,
-    height: i32,
-    _file: std::fs::File,
-    mmap: MmapMut,
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{exit, Command};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use slurp::format::{self, FormatOptions};
+use slurp::{boxes, cli, clipboard, Callbacks, Selection};
+use tracing_subscriber::EnvFilter;
+
+// --live prints at most one line per frame at 60Hz
+const LIVE_INTERVAL: Duration = Duration::from_millis(16);
+const LIVE_DONE_MARKER: &str = "done";
+
+fn write_synced(path: &Path, contents: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
 }
 
-impl State {
-    fn draw(&mut self) {
-        for i in 0..self.outputs.len() {
-            self.draw_on_output(i);
-        }
+// The result shows up in the file all at once, readers never see a partial write
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?.to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(name);
+    let result = write_synced(&temp, contents).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
     }
+    result
+}
 
-    fn draw_on_output(&mut self, output_index: usize) {
-        let selections = self.selections.clone();
-        let start_pos = self.start_pos;
-        let current_pos = self.current_pos;
-
-        if let Some(output_state) = self.outputs.get_mut(output_index) {
-            if let Some(buffer) = output_state.buffer.as_mut() {
-                let width = buffer.width;
-                let height = buffer.height;
-                let stride = cairo::Format::ARgb32.stride_for_width(width as u32).unwrap();
-                let output_pos = output_state.logical_pos;
-
-                let wl_surface = &output_state.surface;
-                let wl_buffer = buffer.pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, &self.qh, ());
-
-                {
-                    let mmap = &mut buffer.mmap[..];
-                    let surface = unsafe { ImageSurface::create_for_data_unsafe(mmap.as_mut_ptr(), Format::ARgb32, width, height, stride).unwrap() };
-                    let ctx = Context::new(&surface).unwrap();
-
-                    // Draw semi-transparent background
-                    ctx.set_source_rgba(0.5, 0.5, 0.5, 0.4);
-                    ctx.set_operator(cairo::Operator::Source);
-                    ctx.paint().unwrap();
-
-                    ctx.set_operator(cairo::Operator::Over);
-
-                    let mut all_selections = selections;
-                    if let Some(start) = start_pos {
-                        let current_selection = get_selection_box(start, current_pos);
-                        all_selections.push(current_selection);
-                    }
-                    draw_selections(&ctx, &all_selections, output_pos);
-
-                    // Translate global mouse pos to local
-                    let local_mouse_x = current_pos.0 - output_pos.0 as f64;
-                    let local_mouse_y = current_pos.1 - output_pos.1 as f64;
-
-                    // Draw crosshair at current mouse position
-                    let crosshair_size = 10.0;
-                    let crosshair_width = 1.0;
-                    ctx.set_source_rgb(1.0, 1.0, 1.0);
-                    ctx.set_line_width(crosshair_width);
-                    ctx.move_to(local_mouse_x - crosshair_size, local_mouse_y);
-                    ctx.line_to(local_mouse_x + crosshair_size, local_mouse_y);
-                    ctx.stroke().unwrap();
-                    ctx.move_to(local_mouse_x, local_mouse_y - crosshair_size);
-                    ctx.line_to(local_mouse_x, local_mouse_y + crosshair_size);
-                    ctx.stroke().unwrap();
-
-                    surface.flush();
-                }
-
-                wl_surface.attach(Some(&wl_buffer), 0, 0);
-                wl_surface.damage_buffer(0, 0, width, height);
-                wl_surface.commit();
-                wl_buffer.destroy();
+// Runs the command once per selection and stops at the first one that fails, whose exit code is returned
+fn run_exec(command: &str, selections: &[Selection], format_options: FormatOptions) -> i32 {
+    let script = format::expand_for_shell(&format::parse(command));
+    for selection in selections {
+        let status = Command::new("sh").arg("-c").arg(&script).envs(selection.shell_variables(format_options)).status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => return status.code().unwrap_or(slurp::EXIT_ERROR),
+            Err(e) => {
+                eprintln!("Error: failed to run '{}': {}", command, e);
+                return slurp::EXIT_ERROR;
             }
         }
     }
+    slurp::EXIT_SUCCESS
 }
 
-fn draw_selections(ctx: &Context, selections: &[(f64, f64, f64, f64)], output_pos: (i32, i32)) {
-    for &(gx, gy, gw, gh) in selections {
-        let local_x = gx - output_pos.0 as f64;
-        let local_y = gy - output_pos.1 as f64;
-
-        // Clear the selection area
-        ctx.set_source_rgba(0.0, 0.0, 0.0, 0.0);
-        ctx.set_operator(cairo::Operator::Source);
-        ctx.rectangle(local_x, local_y, gw, gh);
-        ctx.fill().unwrap();
-
-        // Draw selection border
-        ctx.set_operator(cairo::Operator::Over);
-        ctx.set_source_rgba(0.2, 0.6, 1.0, 0.8);
-        ctx.set_line_width(2.0);
-        ctx.rectangle(local_x, local_y, gw, gh);
-        ctx.stroke().unwrap();
-    }
+#[cfg(feature = "dbus")]
+fn serve_dbus(options: cli::Options) -> ! {
+    let e = match slurp::dbus::serve(options) {
+        Ok(never) => match never {},
+        Err(e) => e,
+    };
+    eprintln!("Error: {}", e);
+    exit(e.exit_code());
 }
 
-fn get_selection_box(p1: (f64, f64), p2: (f64, f64)) -> (f64, f64, f64, f64) {
-    let x = p1.0.min(p2.0);
-    let y = p1.1.min(p2.1);
-    let w = (p1.0 - p2.0).abs();
-    let h = (p1.1 - p2.1).abs();
-    (x, y, w, h)
+#[cfg(not(feature = "dbus"))]
+fn serve_dbus(_: cli::Options) -> ! {
+    eprintln!("Error: this build has no D-Bus support, rebuild with --features dbus");
+    exit(slurp::EXIT_UNSUPPORTED);
 }
 
-impl Dispatch<wl_registry::WlRegistry, ()> for State {
-    fn event(
-        state: &mut Self,
-        registry: &wl_registry::WlRegistry,
-        event: wl_registry::Event,
-        _: &(),
-        _: &Connection,
-        qh: &QueueHandle<Self>,
-    ) {
-        if let wl_registry::Event::Global { name, interface, version } = event {
-            match interface.as_str() {
-                "wl_compositor" => {
-                    state.compositor = Some(registry.bind(name, version, qh, ()));
-                }
-                "wl_shm" => {
-                    state.shm = Some(registry.bind(name, version, qh, ()));
-                }
-                "zwlr_layer_shell_v1" => {
-                    state.layer_shell = Some(registry.bind(name, version, qh, ()));
-                }
-                "zxdg_output_manager_v1" => {
-                    state.xdg_output_manager = Some(registry.bind(name, version, qh, ()));
-                }
-                "wl_seat" => {
-                    let seat: WlSeat = registry.bind(name, version, qh, ());
-                    state.pointer = Some(seat.get_pointer(qh, ()));
-                    state.keyboard = Some(seat.get_keyboard(qh, ()));
-                    state.seat = Some(seat);
-                }
-                "wl_output" => {
-                    let output: WlOutput = registry.bind(name, version, qh, ());
-                    let surface = state.compositor.as_ref().unwrap().create_surface(qh, ());
-                    let layer_surface = state.layer_shell.as_ref().unwrap().get_layer_surface(&surface, Some(&output), zwlr_layer_shell_v1::Layer::Overlay, "rust-slurp".to_string(), qh, ());
-                    layer_surface.set_anchor(zwlr_layer_surface_v1::Anchor::Top | zwlr_layer_surface_v1::Anchor::Right | zwlr_layer_surface_v1::Anchor::Bottom | zwlr_layer_surface_v1::Anchor::Left);
-                    layer_surface.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand);
-                    layer_surface.set_exclusive_zone(-1);
-                    surface.commit();
-
-                    let xdg_output = state.xdg_output_manager.as_ref().unwrap().get_xdg_output(&output, qh, ());
+fn serve_daemon(options: cli::Options) -> ! {
+    let e = match slurp::daemon::serve(options, std::env::args_os().skip(1).collect()) {
+        Ok(never) => match never {},
+        Err(e) => e,
+    };
+    eprintln!("Error: {}", e);
+    exit(e.exit_code());
+}
 
-                    state.outputs.push(OutputState {
-                        output,
-                        xdg_output,
-                        logical_pos: (0, 0),
-                        size: (0, 0),
-                        surface,
-                        layer_surface,
-                        buffer: None,
-                    });
-                }
-                _ => {}
-            }
-        }
-    }
+#[cfg(feature = "control")]
+fn select_with_control(options: cli::Options, boxes: Vec<slurp::CandidateBox>) -> Result<Vec<Selection>, slurp::Error> {
+    slurp::control::select_with_control(options, boxes)
 }
 
-impl Dispatch<wl_compositor::WlCompositor, ()> for State { fn event(_: &mut Self, _: &wl_compositor::WlCompositor, _: wl_compositor::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
-impl Dispatch<wl_shm::WlShm, ()> for State { fn event(_: &mut Self, _: &WlShm, _: wl_shm::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
-impl Dispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for State { fn event(_: &mut Self, _: &ZwlrLayerShellV1, _: zwlr_layer_shell_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
-impl Dispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for State { fn event(_: &mut Self, _: &zxdg_output_manager_v1::ZxdgOutputManagerV1, _: zxdg_output_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+#[cfg(not(feature = "control"))]
+fn select_with_control(_: cli::Options, _: Vec<slurp::CandidateBox>) -> Result<Vec<Selection>, slurp::Error> {
+    eprintln!("Error: this build has no --control support, rebuild with --features control");
+    exit(slurp::EXIT_UNSUPPORTED);
+}
 
-impl Dispatch<wl_seat::WlSeat, ()> for State {
-    fn event(_: &mut Self, _: &WlSeat, _: wl_seat::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+enum LiveEvent {
+    Changed(String),
+    Confirmed,
 }
 
-impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
-    fn event(state: &mut Self, _: &wl_keyboard::WlKeyboard, event: wl_keyboard::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
-        if let wl_keyboard::Event::Key { key, state: key_state, .. } = event {
-            if key == 1 && key_state == WEnum::Value(wl_keyboard::KeyState::Pressed) { // Escape key
-                state.running = false;
-                state.exit_code = 1;
+// Formats the region whenever it changes, the printer decides which of the lines are shown
+fn live_callbacks(format: Option<String>, format_options: FormatOptions, events: Sender<LiveEvent>) -> Callbacks {
+    let confirmed = events.clone();
+    Callbacks::new()
+        .on_selection_changed_with_output(move |selection| {
+            if let Some(selection) = selection {
+                let _ = events.send(LiveEvent::Changed(selection.format_with(format.as_deref(), format_options)));
             }
-        }
-    }
+        })
+        .on_confirmed(move |_| {
+            let _ = confirmed.send(LiveEvent::Confirmed);
+        })
 }
 
-impl Dispatch<wl_pointer::WlPointer, ()> for State {
-    fn event(state: &mut Self, _: &WlPointer, event: wl_pointer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+// Prints at most one line per interval, the last change of a burst once the interval is over. Returns
+// when the callbacks are dropped with the end of the selection.
+fn print_live(events: Receiver<LiveEvent>) {
+    let mut pending: Option<String> = None;
+    let mut last_print: Option<Instant> = None;
+    loop {
+        let event = match (&pending, last_print) {
+            (Some(_), Some(t)) => events.recv_timeout(LIVE_INTERVAL.saturating_sub(t.elapsed())),
+            _ => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
         match event {
-            wl_pointer::Event::Enter { surface, surface_x, surface_y, .. } => {
-                if let Some(index) = state.outputs.iter().position(|o| o.surface.id() == surface.id()) {
-                    state.current_output = Some(index);
-                    let output = &state.outputs[index];
-                    let (ox, oy) = output.logical_pos;
-                    state.current_pos = (ox as f64 + surface_x, oy as f64 + surface_y);
-                    state.draw();
+            Ok(LiveEvent::Changed(line)) => pending = Some(line),
+            Ok(LiveEvent::Confirmed) => {
+                if let Some(line) = pending.take() {
+                    println!("{}", line);
                 }
+                println!("{}", LIVE_DONE_MARKER);
+                last_print = Some(Instant::now());
+                continue;
             }
-            wl_pointer::Event::Leave { .. } => {
-                state.current_output = None;
-            }
-            wl_pointer::Event::Motion { surface_x, surface_y, .. } => {
-                if let Some(output_idx) = state.current_output {
-                    if let Some(output) = state.outputs.get(output_idx) {
-                         let (ox, oy) = output.logical_pos;
-                         state.current_pos = (ox as f64 + surface_x, oy as f64 + surface_y);
-                         if state.start_pos.is_some() {
-                             state.draw();
-                         }
-                    }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if let Some(line) = pending.take() {
+                    println!("{}", line);
                 }
+                return;
             }
-            wl_pointer::Event::Button { button, state: btn_state, .. } => {
-                match button {
-                    272 => { // Left mouse button
-                        if btn_state == WEnum::Value(wl_pointer::ButtonState::Pressed) {
-                            state.start_pos = Some(state.current_pos);
-                        } else { // Released
-                            if let Some(start) = state.start_pos.take() {
-                                let selection = get_selection_box(start, state.current_pos);
-                                if selection.2 > 1.0 && selection.3 > 1.0 {
-                                    println!("{},{} {}x{}", selection.0 as i32, selection.1 as i32, selection.2 as i32, selection.3 as i32);
-                                    state.exit_code = 0;
-                                } else {
-                                    // Selection was just a click or too small, count as cancellation
-                                    state.exit_code = 1;
-                                }
-                                state.running = false;
-                            }
-                        }
-                    }
-                    273 => { // Right mouse button now acts as cancel
-                        state.running = false;
-                        state.exit_code = 1;
-                    }
-                    _ => {}
-                }
+        }
+        if last_print.is_none_or(|t| t.elapsed() >= LIVE_INTERVAL) {
+            if let Some(line) = pending.take() {
+                println!("{}", line);
+                last_print = Some(Instant::now());
             }
-            _ => {}
         }
     }
 }
 
-impl Dispatch<wl_surface::WlSurface, ()> for State { fn event(_: &mut Self, _: &wl_surface::WlSurface, _: wl_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
-
-impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for State {
-    fn event(
-        state: &mut Self,
-        surface: &ZwlrLayerSurfaceV1,
-        event: zwlr_layer_surface_v1::Event,
-        _: &(),
-        _: &Connection,
-        qh: &QueueHandle<Self>,
-    ) {
-        match event {
-            zwlr_layer_surface_v1::Event::Configure { serial, width, height } => {
-                surface.ack_configure(serial);
-                if let Some(output_index) = state.outputs.iter().position(|o| o.layer_surface.id() == surface.id()) {
-                    let output_state = &mut state.outputs[output_index];
-                    if output_state.buffer.is_some() && output_state.buffer.as_ref().unwrap().width == width as i32 && output_state.buffer.as_ref().unwrap().height == height as i32 {
-                        state.draw_on_output(output_index);
-                        return;
-                    }
-
-                    let file = tempfile::tempfile().unwrap();
-                    let stride = cairo::Format::ARgb32.stride_for_width(width).unwrap();
-                    let size = (stride * height as i32) as i32;
-                    file.set_len(size as u64).unwrap();
+// RUST_LOG overrides -v, without either only warnings from libraries are shown
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(io::stderr).init();
+}
 
-                    let pool = state.shm.as_ref().unwrap().create_pool(unsafe { BorrowedFd::borrow_raw(file.as_raw_fd()) }, size, qh, ());
-                    let mmap = unsafe { MmapMut::map_mut(&file).unwrap() };
+fn main() {
+    let options = cli::parse();
+    init_logging(options.verbose);
+    if options.dbus {
+        serve_dbus(options);
+    }
+    if options.daemon {
+        serve_daemon(options);
+    }
+    let boxes = if options.restrict || options.snap_edges.is_some() { boxes::read_boxes(std::io::stdin().lock()) } else { Vec::new() };
+    let format = options.format.clone();
+    let copy = options.copy;
+    let display = options.display.clone();
+    let output_file = options.output_file.clone();
+    let (print0, no_newline) = (options.print0, options.no_newline);
+    let exec = options.exec.clone();
+    let recorder = options.recorder;
+    let control = options.control;
+    let format_options = FormatOptions { float: options.float, pixels: options.pixels };
+    let (callbacks, live_printer) = if options.live {
+        let (sender, receiver) = mpsc::channel();
+        (live_callbacks(format.clone(), format_options, sender), Some(thread::spawn(move || print_live(receiver))))
+    } else {
+        (Callbacks::default(), None)
+    };
 
-                    output_state.buffer = Some(Buffer { pool, width: width as i32, height: height as i32, _file: file, mmap });
-                    state.draw_on_output(output_index);
+    let result = if control { select_with_control(options, boxes) } else { slurp::select_with_callbacks(options, boxes, callbacks) };
+    // The callbacks went away with the selection, so the printer has flushed every line once it returns
+    if let Some(printer) = live_printer {
+        let _ = printer.join();
+    }
+    match result {
+        Ok(selections) if selections.is_empty() => exit(slurp::EXIT_CANCELLED),
+        Ok(selections) => {
+            let lines: Vec<String> = selections
+                .iter()
+                .map(|s| match (recorder, s.single_output) {
+                    (true, true) => s.format_with(Some(format::RECORDER_FORMAT), format_options),
+                    (true, false) => {
+                        eprintln!("Warning: the selection spans several outputs, printing global coordinates");
+                        s.format_with(format.as_deref(), format_options)
+                    }
+                    (false, false) if format_options.pixels => {
+                        eprintln!("Warning: the selection spans several outputs, printing pixels of the one holding most of it");
+                        s.format_with(format.as_deref(), format_options)
+                    }
+                    _ => s.format_with(format.as_deref(), format_options),
+                })
+                .collect();
+            let output = if print0 {
+                lines.iter().map(|line| format!("{}\0", line)).collect()
+            } else if no_newline {
+                lines.join("\n")
+            } else {
+                lines.iter().map(|line| format!("{}\n", line)).collect()
+            };
+            match output_file.as_deref() {
+                Some(path) => {
+                    if let Err(e) = write_atomically(path, &output) {
+                        eprintln!("Error: failed to write {}: {}", path.display(), e);
+                        exit(slurp::EXIT_ERROR);
+                    }
                 }
+                // The command takes over stdout, with --control it carries JSON only
+                None if exec.is_some() || control => {}
+                None => print!("{}", output),
             }
-            zwlr_layer_surface_v1::Event::Closed => {
-                state.running = false;
-                state.exit_code = 1;
+            if copy {
+                if let Err(e) = clipboard::copy_in_background(lines.join("\n"), display.as_deref()) {
+                    eprintln!("Error: {}", e);
+                    exit(e.exit_code());
+                }
             }
-            _ => {}
-        }
-    }
-}
-
-impl Dispatch<WlOutput, ()> for State {
-    fn event(state: &mut Self, output: &WlOutput, event: wl_output::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
-        if let wl_output::Event::Mode { width, height, .. } = event {
-            if let Some(entry) = state.outputs.iter_mut().find(|o| o.output.id() == output.id()) {
-                entry.size = (width as u32, height as u32);
+            match exec {
+                Some(command) => exit(run_exec(&command, &selections, format_options)),
+                None => exit(slurp::EXIT_SUCCESS),
             }
         }
-    }
-}
-
-impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for State {
-    fn event(
-        state: &mut Self,
-        xdg_output: &zxdg_output_v1::ZxdgOutputV1,
-        event: zxdg_output_v1::Event,
-        _: &(),
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
-        if let Some(output_state) = state.outputs.iter_mut().find(|o| o.xdg_output.id() == xdg_output.id()) {
-            match event {
-                zxdg_output_v1::Event::LogicalPosition { x, y } => {
-                    output_state.logical_pos = (x, y);
-                }
-                zxdg_output_v1::Event::LogicalSize { .. } => {}
-                zxdg_output_v1::Event::Done => {}
-                zxdg_output_v1::Event::Name { .. } => {}
-                zxdg_output_v1::Event::Description { .. } => {}
-                _ => {}
-            }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(e.exit_code());
         }
     }
 }
-
-impl Dispatch<WlShmPool, ()> for State { fn event(_: &mut Self, _: &WlShmPool, _: wl_shm_pool::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
-impl Dispatch<wayland_client::protocol::wl_buffer::WlBuffer, ()> for State { fn event(_: &mut Self, _: &wayland_client::protocol::wl_buffer::WlBuffer, _: wayland_client::protocol::wl_buffer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+
+use serde_json::Value;
+
+use crate::boxes::CandidateBox;
+use crate::windows::WindowProvider;
+
+pub struct Niri {
+    socket_path: String,
+}
+
+impl Niri {
+    pub fn from_env() -> Option<Self> {
+        std::env::var("NIRI_SOCKET").ok().map(|socket_path| Niri { socket_path })
+    }
+
+    // Sends a single request and unwraps the {"Ok": {"<Request>": ...}} reply
+    fn request(&self, name: &str) -> Result<Value, String> {
+        let io_error = |e: std::io::Error| format!("request '{}' failed: {}", name, e);
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(|e| format!("failed to connect to {}: {}", self.socket_path, e))?;
+        stream.write_all(format!("\"{}\"\n", name).as_bytes()).map_err(io_error)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).map_err(io_error)?;
+
+        let mut reply: Value = serde_json::from_str(&line).map_err(|e| format!("invalid JSON: {}", e))?;
+        if let Some(err) = reply.get("Err") {
+            return Err(format!("request '{}' failed: {}", name, err));
+        }
+        reply.get_mut("Ok").and_then(|ok| ok.get_mut(name)).map(Value::take).ok_or_else(|| format!("unexpected reply to request '{}'", name))
+    }
+}
+
+impl WindowProvider for Niri {
+    fn name(&self) -> &'static str {
+        "niri"
+    }
+
+    // Returns the windows on the active workspace of each output
+    fn windows(&self) -> Result<Vec<CandidateBox>, String> {
+        Ok(window_boxes(&self.request("Outputs")?, &self.request("Workspaces")?, &self.request("Windows")?))
+    }
+}
+
+fn window_boxes(outputs: &Value, workspaces: &Value, windows: &Value) -> Vec<CandidateBox> {
+    // Window positions are relative to the workspace view, which sits at the output's logical position
+    let workspace_origin = |id: i64| -> Option<(f64, f64)> {
+        let workspace = workspaces.as_array()?.iter().find(|w| w["id"].as_i64() == Some(id))?;
+        if workspace["is_active"].as_bool() != Some(true) {
+            return None;
+        }
+        let logical = &outputs[workspace["output"].as_str()?]["logical"];
+        Some((logical["x"].as_f64()?, logical["y"].as_f64()?))
+    };
+
+    let mut boxes = Vec::new();
+    for window in windows.as_array().into_iter().flatten() {
+        let Some((ox, oy)) = window["workspace_id"].as_i64().and_then(workspace_origin) else {
+            continue;
+        };
+        let layout = &window["layout"];
+        let (Some(tx), Some(ty)) = (layout["tile_pos_in_workspace_view"][0].as_f64(), layout["tile_pos_in_workspace_view"][1].as_f64()) else {
+            continue;
+        };
+        let (dx, dy) = (layout["window_offset_in_tile"][0].as_f64().unwrap_or(0.0), layout["window_offset_in_tile"][1].as_f64().unwrap_or(0.0));
+        if let (Some(w), Some(h)) = (layout["window_size"][0].as_f64(), layout["window_size"][1].as_f64()) {
+            if w > 0.0 && h > 0.0 {
+                boxes.push(CandidateBox {
+                    rect: (ox + tx + dx, oy + ty + dy, w, h),
+                    label: None,
+                    app_id: window["app_id"].as_str().map(str::to_string),
+                    title: window["title"].as_str().map(str::to_string),
+                });
+            }
+        }
+    }
+    boxes
+}
@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use pango::FontDescription;
+
+use crate::color::Color;
+use crate::text;
+
+// Linux evdev codes, as sent in wl_pointer.button
+pub const BTN_LEFT: u32 = 0x110;
+pub const BTN_RIGHT: u32 = 0x111;
+pub const BTN_MIDDLE: u32 = 0x112;
+
+// What a mouse or stylus button does, see --bind
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ButtonAction {
+    // Held to drag a selection, or clicked in the click-to-pick modes
+    StartSelection,
+    Cancel,
+    // Like Enter
+    Confirm,
+    // Selects the candidate box under the cursor
+    PickBox,
+    // Selects the candidate box under the cursor, or else the whole output
+    Pick,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum RenderBackend {
+    // Cairo into shared memory buffers
+    Cpu,
+    // EGL window surfaces, the dim layer and the selections are filled by the GPU while cairo still draws
+    // borders and labels
+    Gpu,
+}
+
+// Colors and dimming set for one output with --output-theme, unset ones come from the global options
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputTheme {
+    pub background_color: Option<Color>,
+    pub dim_opacity: Option<f64>,
+    pub border_color: Option<Color>,
+    pub selection_color: Option<Color>,
+    pub box_color: Option<Color>,
+}
+
+// What an output's overlay is drawn with, resolved when it's drawn so --control color changes still apply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub no_dim: bool,
+    pub background_color: Color,
+    pub border_color: Color,
+    pub selection_color: Color,
+    pub box_color: Color,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub no_dim: bool,
+    pub background_color: Color,
+    pub border_color: Color,
+    pub selection_color: Color,
+    pub box_color: Color,
+    pub border_weight: f64,
+    pub format: Option<String>,
+    pub point: bool,
+    pub output: bool,
+    pub restrict: bool,
+    pub aspect_ratio: Option<f64>,
+    pub fixed_size: Option<(f64, f64)>,
+    pub step: f64,
+    pub multiple: bool,
+    pub edit: bool,
+    pub initial: Option<(f64, f64, f64, f64)>,
+    pub snap: Option<f64>,
+    pub show_grid: bool,
+    pub snap_edges: Option<f64>,
+    pub windows: bool,
+    pub freeze: bool,
+    pub magnifier: Option<f64>,
+    pub show_dimensions: bool,
+    pub show_position: bool,
+    pub font: FontDescription,
+    pub guides: bool,
+    pub border_dash: Option<Vec<f64>>,
+    pub corner_radius: f64,
+    pub handle_signals: bool,
+    pub timeout: Option<Duration>,
+    pub confirm: bool,
+    pub live: bool,
+    pub copy: bool,
+    pub output_file: Option<PathBuf>,
+    pub print0: bool,
+    pub no_newline: bool,
+    pub exec: Option<String>,
+    pub capture: Option<PathBuf>,
+    pub recorder: bool,
+    pub dbus: bool,
+    pub daemon: bool,
+    pub socket: Option<PathBuf>,
+    pub control: bool,
+    pub renderer: RenderBackend,
+    pub verbose: u8,
+    pub hide_cursor: bool,
+    pub grab_keyboard: bool,
+    pub inhibit_shortcuts: bool,
+    pub min_size: Option<(f64, f64)>,
+    pub float: bool,
+    pub pixels: bool,
+    pub single_output: bool,
+    pub button_bindings: Vec<(u32, ButtonAction)>,
+    pub scroll_step: f64,
+    pub output_themes: Vec<(String, OutputTheme)>,
+    pub display: Option<String>,
+    pub outputs: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            no_dim: false,
+            background_color: Color::new(0.5, 0.5, 0.5, 0.4),
+            border_color: Color::new(0.2, 0.6, 1.0, 0.8),
+            selection_color: Color::new(0.0, 0.0, 0.0, 0.0),
+            box_color: Color::new(0.2, 0.6, 1.0, 0.4),
+            border_weight: 2.0,
+            format: None,
+            point: false,
+            output: false,
+            restrict: false,
+            aspect_ratio: None,
+            fixed_size: None,
+            step: 10.0,
+            multiple: false,
+            edit: false,
+            initial: None,
+            snap: None,
+            show_grid: false,
+            snap_edges: None,
+            windows: false,
+            freeze: false,
+            magnifier: None,
+            show_dimensions: false,
+            show_position: false,
+            font: FontDescription::from_string(text::DEFAULT_FONT),
+            guides: false,
+            border_dash: None,
+            corner_radius: 0.0,
+            handle_signals: false,
+            timeout: None,
+            confirm: false,
+            live: false,
+            copy: false,
+            output_file: None,
+            print0: false,
+            no_newline: false,
+            exec: None,
+            capture: None,
+            recorder: false,
+            dbus: false,
+            daemon: false,
+            socket: None,
+            control: false,
+            renderer: RenderBackend::Cpu,
+            verbose: 0,
+            hide_cursor: false,
+            grab_keyboard: false,
+            inhibit_shortcuts: false,
+            min_size: None,
+            float: false,
+            pixels: false,
+            single_output: false,
+            button_bindings: vec![(BTN_LEFT, ButtonAction::StartSelection), (BTN_MIDDLE, ButtonAction::Pick), (BTN_RIGHT, ButtonAction::Cancel)],
+            scroll_step: 10.0,
+            output_themes: Vec::new(),
+            display: None,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    pub(crate) fn theme(&self, output_name: Option<&str>) -> Theme {
+        let mut theme = Theme { no_dim: self.no_dim, background_color: self.background_color, border_color: self.border_color, selection_color: self.selection_color, box_color: self.box_color };
+        let overrides = self.output_themes.iter().filter(|(name, _)| Some(name.as_str()) == output_name).map(|(_, t)| t);
+        for t in overrides {
+            if let Some(color) = t.background_color {
+                theme.background_color = color;
+            }
+            // An opacity brings the dimming back on an output even with --no-dim
+            if let Some(opacity) = t.dim_opacity {
+                theme.background_color.a = opacity;
+                theme.no_dim = opacity == 0.0;
+            }
+            theme.border_color = t.border_color.unwrap_or(theme.border_color);
+            theme.selection_color = t.selection_color.unwrap_or(theme.selection_color);
+            theme.box_color = t.box_color.unwrap_or(theme.box_color);
+        }
+        theme
+    }
+}
@@ -0,0 +1,744 @@
+use std::f64::consts::PI;
+use std::os::unix::io::{AsRawFd, BorrowedFd};
+
+use cairo::{Context, Format, ImageSurface};
+use memmap2::MmapMut;
+use wayland_client::protocol::{
+    wl_buffer::WlBuffer,
+    wl_output,
+    wl_shm::{self, WlShm},
+    wl_shm_pool::WlShmPool,
+    wl_surface::WlSurface,
+};
+use tracing::debug;
+use wayland_client::QueueHandle;
+
+use crate::color::Color;
+use crate::geometry::{buffer_rect, grow_rect, handle_points, intersect_rect, to_local};
+use crate::options::Theme;
+use crate::screencopy::Capture;
+use crate::wayland::OutputState;
+use crate::{shm, text, CandidateBox, Error, Options, Rect, State};
+
+pub(crate) const HANDLE_SIZE: f64 = 8.0;
+const CROSSHAIR_SIZE: f64 = 10.0;
+const REJECTED_BORDER_COLOR: Color = Color::new(0.9, 0.2, 0.2, 0.9);
+// Enough room for a text label next to the point it belongs to
+const LABEL_EXTENT: f64 = 160.0;
+pub(crate) const BUTTON_SIZE: f64 = 24.0;
+pub(crate) const BUTTON_GAP: f64 = 6.0;
+
+// Rects in buffer pixels
+pub(crate) type BufferRect = (i32, i32, i32, i32);
+
+// What renderers that composite the background paint underneath the cairo frame
+pub(crate) struct Background<'a> {
+    // None when dimming is disabled
+    pub dim: Option<Color>,
+    pub frozen: Option<&'a Capture>,
+    pub transform: wl_output::Transform,
+    pub logical_size: (i32, i32),
+    // Selections in buffer pixels with their corner radius, replaced with the selection color
+    pub fills: Vec<((f64, f64, f64, f64), f64)>,
+    pub fill_color: Color,
+}
+
+// Allocates the render targets of the outputs
+pub(crate) trait Renderer {
+    // (Re)creates the output's target at its buffer size, true when the targets of all outputs were replaced
+    fn allocate(&mut self, outputs: &mut [OutputState], index: usize, qh: &QueueHandle<State>) -> Result<bool, Error>;
+}
+
+// Where an output's frames are drawn with cairo and handed to the compositor from
+pub(crate) trait RenderTarget {
+    fn size(&self) -> (i32, i32);
+    // Whether the next frame has to be drawn in full
+    fn invalid(&self) -> bool;
+    fn invalidate(&mut self);
+    // The dim layer, frozen frame and selection fills are left out of the cairo frame when true
+    fn composites_background(&self) -> bool {
+        false
+    }
+    // Context in buffer pixels over the next frame and the rects of it to redraw, None redraws all of it.
+    // Returns None while no buffer is free.
+    fn begin(&mut self, changed: &[BufferRect]) -> Result<Option<(Context, Option<Vec<BufferRect>>)>, Error>;
+    // Attaches the frame and commits, damaging changed or all of it when full
+    fn present(&mut self, surface: &WlSurface, changed: &[BufferRect], full: bool, background: &Background) -> Result<(), Error>;
+    // False when the buffer isn't one of the target's
+    fn release(&mut self, _buffer: &WlBuffer) -> bool {
+        false
+    }
+    fn destroy(&mut self);
+}
+
+// One memfd and wl_shm_pool back the buffers of every output, each output gets a slice of it
+pub(crate) struct ShmRenderer {
+    shm: WlShm,
+    pool: Option<ShmPool>,
+}
+
+struct ShmPool {
+    pool: WlShmPool,
+    size: usize,
+    file: std::fs::File,
+    mmap: MmapMut,
+}
+
+// Two buffers in the output's slice of the pool, one is drawn while the compositor may still read the other
+struct Buffer {
+    width: i32,
+    height: i32,
+    slots: [BufferSlot; 2],
+    // The slot between begin and present
+    current: usize,
+}
+
+struct BufferSlot {
+    buffer: WlBuffer,
+    // Drawing state over the slot's pixels, kept until the pool is laid out again
+    context: Context,
+    // Attached and not yet released by the compositor
+    busy: bool,
+    // Buffer pixels changed since this buffer was last drawn, None when all of it has to be drawn
+    stale: Option<Vec<BufferRect>>,
+}
+
+impl ShmRenderer {
+    pub fn new(shm: WlShm) -> Self {
+        Self { shm, pool: None }
+    }
+}
+
+impl Renderer for ShmRenderer {
+    // Slices the shared pool between all outputs again, growing it when needed. Every output gets
+    // new buffers and is drawn in full, which only happens when an output appears or changes size.
+    fn allocate(&mut self, outputs: &mut [OutputState], _index: usize, qh: &QueueHandle<State>) -> Result<bool, Error> {
+        let mut sizes = Vec::with_capacity(outputs.len());
+        let mut total = 0;
+        for output_state in outputs.iter() {
+            let size = match output_state.buffer_size() {
+                Some((width, height)) => Some((width, height, Format::ARgb32.stride_for_width(width as u32)?)),
+                None => None,
+            };
+            sizes.push(size.map(|size| (size, total)));
+            total += size.map_or(0, |(_, height, stride)| 2 * (stride * height) as usize);
+        }
+        // The contexts point into the mapping, so they go before it's replaced
+        for output_state in outputs.iter_mut() {
+            if let Some(mut old) = output_state.target.take() {
+                old.destroy();
+            }
+            output_state.drawn_regions.clear();
+        }
+
+        match self.pool.as_mut() {
+            Some(pool) if pool.size >= total => {}
+            // Pools can only grow, the sealed memfd can too
+            Some(pool) => {
+                debug!(from = pool.size, to = total, "growing shm pool");
+                pool.file.set_len(total as u64)?;
+                pool.pool.resize(total as i32);
+                pool.mmap = unsafe { MmapMut::map_mut(&pool.file)? };
+                pool.size = total;
+            }
+            None => {
+                debug!(size = total, "creating shm pool");
+                let file = shm::create(total)?;
+                let mmap = unsafe { MmapMut::map_mut(&file)? };
+                let pool = self.shm.create_pool(unsafe { BorrowedFd::borrow_raw(file.as_raw_fd()) }, total as i32, qh, ());
+                self.pool = Some(ShmPool { pool, size: total, file, mmap });
+            }
+        }
+        let pool = self.pool.as_mut().unwrap();
+
+        for (output_state, size) in outputs.iter_mut().zip(sizes) {
+            let Some(((width, height, stride), offset)) = size else {
+                continue;
+            };
+            let size = stride * height;
+            let mut slot = |i: i32| -> Result<BufferSlot, Error> {
+                let data = pool.mmap[offset + (i * size) as usize..].as_mut_ptr();
+                let surface = unsafe { ImageSurface::create_for_data_unsafe(data, Format::ARgb32, width, height, stride)? };
+                Ok(BufferSlot {
+                    buffer: pool.pool.create_buffer(offset as i32 + i * size, width, height, stride, wl_shm::Format::Argb8888, qh, ()),
+                    context: Context::new(&surface)?,
+                    busy: false,
+                    stale: None,
+                })
+            };
+            let slots = [slot(0)?, slot(1)?];
+            output_state.target = Some(Box::new(Buffer { width, height, slots, current: 0 }));
+        }
+        Ok(true)
+    }
+}
+
+impl RenderTarget for Buffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn invalid(&self) -> bool {
+        self.slots.iter().any(|s| s.stale.is_none())
+    }
+
+    // Both buffers get drawn in full next
+    fn invalidate(&mut self) {
+        for slot in &mut self.slots {
+            slot.stale = None;
+        }
+    }
+
+    // Only what changed since the chosen buffer was last drawn is redrawn
+    fn begin(&mut self, changed: &[BufferRect]) -> Result<Option<(Context, Option<Vec<BufferRect>>)>, Error> {
+        // Both buffers are still with the compositor, releasing one of them redraws
+        let Some(slot) = self.slots.iter().position(|s| !s.busy) else {
+            return Ok(None);
+        };
+        for s in &mut self.slots {
+            if let Some(stale) = s.stale.as_mut() {
+                stale.extend(changed);
+            }
+        }
+        self.current = slot;
+        let redraw = self.slots[slot].stale.replace(Vec::new());
+        Ok(Some((self.slots[slot].context.clone(), redraw)))
+    }
+
+    fn present(&mut self, surface: &WlSurface, changed: &[BufferRect], full: bool, _: &Background) -> Result<(), Error> {
+        let slot = &mut self.slots[self.current];
+        slot.context.target().flush();
+        surface.attach(Some(&slot.buffer), 0, 0);
+        if full {
+            surface.damage_buffer(0, 0, self.width, self.height);
+        } else {
+            for &(x, y, w, h) in changed {
+                surface.damage_buffer(x, y, w, h);
+            }
+        }
+        surface.commit();
+        slot.busy = true;
+        Ok(())
+    }
+
+    fn release(&mut self, buffer: &WlBuffer) -> bool {
+        let Some(slot) = self.slots.iter_mut().find(|s| s.buffer == *buffer) else {
+            return false;
+        };
+        slot.busy = false;
+        true
+    }
+
+    fn destroy(&mut self) {
+        for slot in &self.slots {
+            slot.buffer.destroy();
+        }
+    }
+}
+
+impl State {
+    // (Re)allocates the output's buffer at pixel resolution when its logical size or scale changed,
+    // true when the buffers of all outputs were replaced
+    fn allocate_buffer(&mut self, output_index: usize) -> Result<bool, Error> {
+        let output_state = &self.outputs[output_index];
+        let Some(size) = output_state.buffer_size() else {
+            return Ok(false);
+        };
+        if output_state.target.as_ref().is_some_and(|t| t.size() == size) {
+            return Ok(false);
+        }
+        debug!(output = ?output_state.name, size = ?size, "allocating buffer");
+        self.renderer.allocate(&mut self.outputs, output_index, &self.qh)
+    }
+
+    pub(crate) fn draw(&mut self) {
+        // Every change to the selection is followed by a redraw, so this is where it's reported
+        let selection = self.current_selection().or(self.edit_rect);
+        if selection != self.reported_selection {
+            self.reported_selection = selection;
+            if let Some(selection_changed) = self.callbacks.selection_changed.as_mut() {
+                selection_changed(selection);
+            }
+            if self.callbacks.selection_changed_with_output.is_some() {
+                let result = selection.map(|rect| self.selection_result(rect, None));
+                if let Some(selection_changed) = self.callbacks.selection_changed_with_output.as_mut() {
+                    selection_changed(result.as_ref());
+                }
+            }
+        }
+        // The selection usually only touches one output, the others would redraw the same frame
+        let regions = self.dynamic_regions();
+        for i in 0..self.outputs.len() {
+            if self.outputs[i].affected_by(&regions) {
+                self.draw_on_output(i);
+            }
+        }
+    }
+
+    // Draws a frame, first (re)allocating the buffer if the output's size or scale changed
+    pub(crate) fn draw_on_output(&mut self, output_index: usize) {
+        let output_state = &mut self.outputs[output_index];
+        if !output_state.configured {
+            return;
+        }
+        // Until the compositor asks for the next frame changes only pile up, then they're drawn at once
+        if output_state.frame_pending {
+            output_state.redraw_pending = true;
+            return;
+        }
+        match self.allocate_buffer(output_index) {
+            // The other outputs lost their buffers too
+            Ok(true) => {
+                for i in (0..self.outputs.len()).filter(|&i| i != output_index) {
+                    self.draw_on_output(i);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => return self.fail(e),
+        }
+        if let Err(e) = self.render_output(output_index) {
+            self.fail(e);
+        }
+    }
+
+    // Everything drawn that depends on the pointer or the selections, in global logical coordinates.
+    // Whatever changes between two frames lies within the old and the new regions.
+    fn dynamic_regions(&self) -> Vec<Rect> {
+        let (x, y) = self.current_pos;
+        let around_cursor = |extent: f64| (x - extent, y - extent, 2.0 * extent, 2.0 * extent);
+        let mut regions = vec![around_cursor(CROSSHAIR_SIZE + 1.0)];
+        if self.options.show_position || self.prompt.is_some() {
+            regions.push(around_cursor(LABEL_EXTENT));
+        }
+        if self.options.magnifier.is_some() {
+            regions.push(around_cursor(2.0 * MAGNIFIER_RADIUS + 24.0));
+        }
+        if let (true, Some((bx, by, bw, bh))) = (self.options.guides, self.outputs_bounds()) {
+            regions.push((x - 1.0, by, 2.0, bh));
+            regions.push((bx, y - 1.0, bw, 2.0));
+        }
+
+        // Borders, handles and the confirm buttons reach outside of a selection
+        let margin = self.options.border_weight.max(HANDLE_SIZE) + BUTTON_GAP + BUTTON_SIZE + 2.0;
+        let current = self.current_selection();
+        let fixed = self.fixed_size_rect();
+        let hovered = self.hovered_box().map(|b| b.rect);
+        let selections = self.selections.iter().chain(&current).chain(&self.edit_rect).chain(&fixed).chain(&hovered);
+        regions.extend(selections.map(|&rect| grow_rect(rect, margin)));
+        if let (true, Some(rect)) = (self.options.show_dimensions, current) {
+            regions.push(grow_rect(rect, LABEL_EXTENT));
+        }
+
+        if self.options.output {
+            regions.extend(self.current_output.and_then(|i| self.outputs.get(i)).map(|o| o.logical_rect()));
+        }
+        // The focus outline runs along the edges of the focused output
+        if let (true, Some(output)) = (self.outputs.len() > 1, self.outputs.get(self.focused_output)) {
+            let (ox, oy, ow, oh) = output.logical_rect();
+            regions.extend([(ox, oy, ow, 3.0), (ox, oy + oh - 3.0, ow, 3.0), (ox, oy, 3.0, oh), (ox + ow - 3.0, oy, 3.0, oh)]);
+        }
+        regions
+    }
+
+    fn render_output(&mut self, output_index: usize) -> Result<(), Error> {
+        let regions = self.dynamic_regions();
+        let selections = self.selections.clone();
+        let current_pos = self.current_pos;
+        let end_pos = self.end_pos;
+        let current_selection = self.current_selection();
+        let fixed_rect = self.fixed_size_rect();
+        let show_focus = self.outputs.len() > 1 && self.focused_output == output_index;
+        let hovered = self.hovered_box().map(|b| b.rect);
+
+        if let Some(output_state) = self.outputs.get_mut(output_index) {
+            if let Some(target) = output_state.target.as_mut() {
+                let (width, height) = target.size();
+                let output_pos = output_state.logical_pos;
+                let output_rect = output_state.logical_rect();
+                let theme = self.options.theme(output_state.name.as_deref());
+                // Everything below is drawn in logical coordinates, cairo scales it to the buffer.
+                // Pointer positions are surface-local logical coordinates with either scaling method.
+                let (logical_width, logical_height) = (output_state.configured_size.0 as i32, output_state.configured_size.1 as i32);
+
+                // Only what changed since the chosen buffer was last drawn is redrawn, and only what changed
+                // since the previous frame is damaged
+                let buffer_scale = (width as f64 / logical_width as f64, height as f64 / logical_height as f64);
+                let local = |rect: Rect| intersect_rect(rect, output_rect).map(|(x, y, w, h)| buffer_rect((x - output_rect.0, y - output_rect.1, w, h), buffer_scale));
+                let changed: Vec<(i32, i32, i32, i32)> = output_state.drawn_regions.iter().chain(&regions).filter_map(|&rect| local(rect)).collect();
+                let Some((ctx, redraw)) = target.begin(&changed)? else {
+                    output_state.redraw_pending = true;
+                    return Ok(());
+                };
+                output_state.redraw_pending = false;
+                output_state.drawn_regions = regions;
+                let composites_background = target.composites_background();
+                let mut fills = Vec::new();
+
+                {
+                    // Clip and scale of the previous frame are dropped with the restore below
+                    ctx.save()?;
+                    // Clipped in buffer pixels, before scaling, so the edges don't get antialiased
+                    if let Some(redraw) = &redraw {
+                        for &(x, y, w, h) in redraw {
+                            ctx.rectangle(x as f64, y as f64, w as f64, h as f64);
+                        }
+                        ctx.clip();
+                    }
+                    ctx.scale(buffer_scale.0, buffer_scale.1);
+
+                    // Draw semi-transparent background, or just clear the buffer when dimming is disabled.
+                    // With --freeze the captured frame goes underneath and the dim layer on top of it.
+                    // Renderers compositing the background get a transparent frame to lay over it.
+                    ctx.set_operator(cairo::Operator::Source);
+                    let capture = self.captures.iter().find(|c| c.global_name == output_state.global_name);
+                    let frozen = match capture {
+                        Some(c) if self.options.freeze && !composites_background => c.paint(&ctx, output_state.transform, (logical_width, logical_height), cairo::Filter::Good)?,
+                        _ => false,
+                    };
+                    if composites_background {
+                        ctx.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+                        ctx.paint()?;
+                    } else if frozen {
+                        ctx.set_operator(cairo::Operator::Over);
+                        if !theme.no_dim {
+                            set_source_color(&ctx, theme.background_color);
+                            ctx.paint()?;
+                        }
+                    } else {
+                        if theme.no_dim {
+                            ctx.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+                        } else {
+                            set_source_color(&ctx, theme.background_color);
+                        }
+                        ctx.paint()?;
+                    }
+
+                    ctx.set_operator(cairo::Operator::Over);
+
+                    if self.options.show_grid {
+                        if let Some(grid) = self.options.snap {
+                            draw_grid(&ctx, grid, output_pos, (logical_width, logical_height), &theme)?;
+                        }
+                    }
+
+                    // The selections are cut out of the frame for the renderer to fill
+                    let fill_color = if composites_background { Color::new(0.0, 0.0, 0.0, 0.0) } else { theme.selection_color };
+                    draw_boxes(&ctx, &self.boxes, hovered, output_pos, fill_color, &theme, &self.options)?;
+
+                    let mut all_selections = selections;
+                    // In output mode the hovered output is shown as selected
+                    if self.options.output && self.current_output == Some(output_index) {
+                        all_selections.push(output_rect);
+                    }
+                    if let Some(rect) = fixed_rect {
+                        all_selections.push(rect);
+                    }
+                    if let Some(selection) = current_selection {
+                        all_selections.push(selection);
+                    }
+                    if let Some(rect) = self.edit_rect {
+                        all_selections.push(rect);
+                    }
+                    let rejected = current_selection.filter(|&s| self.below_min_size(s));
+                    draw_selections(&ctx, &all_selections, rejected, output_pos, fill_color, &theme, &self.options)?;
+                    if composites_background {
+                        fills = all_selections
+                            .iter()
+                            .chain(&hovered)
+                            .map(|&(x, y, w, h)| {
+                                let (x, y) = (x - output_pos.0 as f64, y - output_pos.1 as f64);
+                                ((x * buffer_scale.0, y * buffer_scale.1, w * buffer_scale.0, h * buffer_scale.1), self.options.corner_radius * buffer_scale.0)
+                            })
+                            .collect();
+                    }
+                    if let (true, Some(rect)) = (self.options.edit, self.edit_rect) {
+                        draw_handles(&ctx, rect, output_pos, &theme)?;
+                    }
+                    if let (true, Some(rect)) = (self.options.confirm, self.edit_rect) {
+                        let (accept, cancel) = self.confirm_buttons(rect);
+                        draw_confirm_buttons(&ctx, accept, cancel, output_pos)?;
+                    }
+
+                    // Outline the focused output so keyboard users can tell where the cursor is
+                    if show_focus {
+                        set_source_color(&ctx, theme.border_color);
+                        ctx.set_line_width(2.0);
+                        ctx.rectangle(1.0, 1.0, logical_width as f64 - 2.0, logical_height as f64 - 2.0);
+                        ctx.stroke()?;
+                    }
+
+                    // Translate global mouse pos to local
+                    let (local_mouse_x, local_mouse_y) = to_local(current_pos, output_pos);
+
+                    // Guide lines span every output at the cursor's global x and y
+                    if self.options.guides {
+                        ctx.set_source_rgba(1.0, 1.0, 1.0, 0.5);
+                        ctx.set_line_width(1.0);
+                        if (0.0..logical_width as f64).contains(&local_mouse_x) {
+                            ctx.move_to(local_mouse_x, 0.0);
+                            ctx.line_to(local_mouse_x, logical_height as f64);
+                        }
+                        if (0.0..logical_height as f64).contains(&local_mouse_y) {
+                            ctx.move_to(0.0, local_mouse_y);
+                            ctx.line_to(logical_width as f64, local_mouse_y);
+                        }
+                        ctx.stroke()?;
+                    }
+
+                    // Draw crosshair at current mouse position
+                    let crosshair_size = CROSSHAIR_SIZE;
+                    let crosshair_width = 1.0;
+                    ctx.set_source_rgb(1.0, 1.0, 1.0);
+                    ctx.set_line_width(crosshair_width);
+                    ctx.move_to(local_mouse_x - crosshair_size, local_mouse_y);
+                    ctx.line_to(local_mouse_x + crosshair_size, local_mouse_y);
+                    ctx.stroke()?;
+                    ctx.move_to(local_mouse_x, local_mouse_y - crosshair_size);
+                    ctx.line_to(local_mouse_x, local_mouse_y + crosshair_size);
+                    ctx.stroke()?;
+
+                    let cursor_on_output = (0.0..logical_width as f64).contains(&local_mouse_x) && (0.0..logical_height as f64).contains(&local_mouse_y);
+                    if self.options.show_position && cursor_on_output {
+                        let position = format!("{}, {}", current_pos.0.floor() as i32, current_pos.1.floor() as i32);
+                        text::draw_label(&ctx, &position, &self.options.font, (local_mouse_x, local_mouse_y), (1.0, 1.0), (logical_width, logical_height))?;
+                    }
+
+                    // The coordinate prompt sits above the cursor, out of the way of the position label
+                    if let (Some(prompt), true) = (&self.prompt, cursor_on_output) {
+                        text::draw_label(&ctx, &format!(":{}_", prompt), &self.options.font, (local_mouse_x, local_mouse_y), (1.0, -1.0), (logical_width, logical_height))?;
+                    }
+
+                    if let (true, Some((x, y, w, h))) = (self.options.show_dimensions, current_selection) {
+                        // Label the corner that follows the cursor, outside of the selection
+                        let right = end_pos.0 >= x + w / 2.0;
+                        let bottom = end_pos.1 >= y + h / 2.0;
+                        let corner_x = if right { x + w } else { x } - output_pos.0 as f64;
+                        let corner_y = if bottom { y + h } else { y } - output_pos.1 as f64;
+                        if (0.0..=logical_width as f64).contains(&corner_x) && (0.0..=logical_height as f64).contains(&corner_y) {
+                            let direction = (if right { 1.0 } else { -1.0 }, if bottom { 1.0 } else { -1.0 });
+                            text::draw_label(&ctx, &format!("{}x{}", w.round() as i32, h.round() as i32), &self.options.font, (corner_x, corner_y), direction, (logical_width, logical_height))?;
+                        }
+                    }
+
+                    if let (Some(zoom), Some(capture)) = (self.options.magnifier, capture) {
+                        if cursor_on_output {
+                            draw_magnifier(&ctx, capture, output_state.transform, (local_mouse_x, local_mouse_y), (logical_width, logical_height), zoom, &theme)?;
+                        }
+                    }
+
+                    ctx.restore()?;
+                }
+
+                let wl_surface = &output_state.surface;
+                // A buffer scale left from integer scaling would shrink the buffer below the viewport's source
+                match output_state.viewport.as_ref() {
+                    Some(viewport) if output_state.preferred_scale.is_some() => {
+                        wl_surface.set_buffer_scale(1);
+                        viewport.set_destination(logical_width, logical_height);
+                    }
+                    _ => wl_surface.set_buffer_scale(output_state.scale),
+                }
+                wl_surface.frame(&self.qh, output_state.global_name);
+                output_state.frame_pending = true;
+                let background = Background {
+                    dim: (!theme.no_dim).then_some(theme.background_color),
+                    frozen: self.captures.iter().find(|c| self.options.freeze && c.global_name == output_state.global_name),
+                    transform: output_state.transform,
+                    logical_size: (logical_width, logical_height),
+                    fills,
+                    fill_color: theme.selection_color,
+                };
+                target.present(wl_surface, &changed, redraw.is_none(), &background)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_source_color(ctx: &Context, color: Color) {
+    ctx.set_source_rgba(color.r, color.g, color.b, color.a);
+}
+
+// A drag below --min-size is passed as rejected and outlined in red even without a border
+fn draw_selections(ctx: &Context, selections: &[(f64, f64, f64, f64)], rejected: Option<(f64, f64, f64, f64)>, output_pos: (i32, i32), fill_color: Color, theme: &Theme, options: &Options) -> Result<(), cairo::Error> {
+    for &(gx, gy, gw, gh) in selections {
+        let local_x = gx - output_pos.0 as f64;
+        let local_y = gy - output_pos.1 as f64;
+
+        // Replace the selection area with the selection color (transparent by default)
+        set_source_color(ctx, fill_color);
+        ctx.set_operator(cairo::Operator::Source);
+        rounded_rectangle(ctx, (local_x, local_y, gw, gh), options.corner_radius);
+        ctx.fill()?;
+
+        // Draw selection border
+        ctx.set_operator(cairo::Operator::Over);
+        let (border_color, border_weight) = if rejected == Some((gx, gy, gw, gh)) { (REJECTED_BORDER_COLOR, options.border_weight.max(1.0)) } else { (theme.border_color, options.border_weight) };
+        if border_weight > 0.0 {
+            set_source_color(ctx, border_color);
+            ctx.set_line_width(border_weight);
+            if let Some(dash) = &options.border_dash {
+                ctx.set_dash(dash, 0.0);
+            }
+            rounded_rectangle(ctx, (local_x, local_y, gw, gh), options.corner_radius);
+            ctx.stroke()?;
+            ctx.set_dash(&[], 0.0);
+        }
+    }
+    Ok(())
+}
+
+fn rounded_rectangle(ctx: &Context, (x, y, w, h): (f64, f64, f64, f64), radius: f64) {
+    let r = radius.min(w / 2.0).min(h / 2.0);
+    if r <= 0.0 {
+        ctx.rectangle(x, y, w, h);
+        return;
+    }
+    ctx.new_sub_path();
+    ctx.arc(x + w - r, y + r, r, -PI / 2.0, 0.0);
+    ctx.arc(x + w - r, y + h - r, r, 0.0, PI / 2.0);
+    ctx.arc(x + r, y + h - r, r, PI / 2.0, PI);
+    ctx.arc(x + r, y + r, r, PI, 3.0 * PI / 2.0);
+    ctx.close_path();
+}
+
+// The hovered box is cut out of the dim layer like a selection and outlined in the border color
+fn draw_boxes(ctx: &Context, boxes: &[CandidateBox], hovered: Option<Rect>, output_pos: (i32, i32), fill_color: Color, theme: &Theme, options: &Options) -> Result<(), cairo::Error> {
+    let local = |(gx, gy, gw, gh): Rect| (gx - output_pos.0 as f64, gy - output_pos.1 as f64, gw, gh);
+    if let Some(rect) = hovered {
+        set_source_color(ctx, fill_color);
+        ctx.set_operator(cairo::Operator::Source);
+        rounded_rectangle(ctx, local(rect), options.corner_radius);
+        ctx.fill()?;
+        ctx.set_operator(cairo::Operator::Over);
+    }
+    set_source_color(ctx, theme.box_color);
+    ctx.set_line_width(options.border_weight.max(1.0));
+    for b in boxes {
+        let (x, y, w, h) = local(b.rect);
+        ctx.rectangle(x, y, w, h);
+        ctx.stroke()?;
+    }
+    if let Some(rect) = hovered {
+        let (x, y, w, h) = local(rect);
+        set_source_color(ctx, theme.border_color);
+        ctx.set_line_width(options.border_weight.max(1.0) + 1.0);
+        ctx.rectangle(x, y, w, h);
+        ctx.stroke()?;
+    }
+    for b in boxes {
+        if let Some(label) = &b.label {
+            text::draw_tag(ctx, label, &options.font, (b.rect.0 - output_pos.0 as f64, b.rect.1 - output_pos.1 as f64))?;
+        }
+    }
+    Ok(())
+}
+
+// Grid lines are aligned to global coordinates so they line up across outputs
+fn draw_grid(ctx: &Context, grid: f64, output_pos: (i32, i32), size: (i32, i32), theme: &Theme) -> Result<(), cairo::Error> {
+    let color = theme.border_color;
+    ctx.set_source_rgba(color.r, color.g, color.b, color.a * 0.2);
+    ctx.set_line_width(1.0);
+
+    let (ox, oy) = (output_pos.0 as f64, output_pos.1 as f64);
+    let mut x = (ox / grid).ceil() * grid - ox;
+    while x < size.0 as f64 {
+        ctx.move_to(x + 0.5, 0.0);
+        ctx.line_to(x + 0.5, size.1 as f64);
+        x += grid;
+    }
+    let mut y = (oy / grid).ceil() * grid - oy;
+    while y < size.1 as f64 {
+        ctx.move_to(0.0, y + 0.5);
+        ctx.line_to(size.0 as f64, y + 0.5);
+        y += grid;
+    }
+    ctx.stroke()?;
+    Ok(())
+}
+
+const MAGNIFIER_RADIUS: f64 = 60.0;
+
+// Zoomed view of the captured frame around the cursor, kept next to the crosshair and away from the output edges
+fn draw_magnifier(ctx: &Context, capture: &Capture, transform: wl_output::Transform, cursor: (f64, f64), size: (i32, i32), zoom: f64, theme: &Theme) -> Result<(), cairo::Error> {
+    let radius = MAGNIFIER_RADIUS;
+    let offset = radius + 20.0;
+    let cx = if cursor.0 + offset + radius > size.0 as f64 { cursor.0 - offset } else { cursor.0 + offset };
+    let cy = if cursor.1 + offset + radius > size.1 as f64 { cursor.1 - offset } else { cursor.1 + offset };
+    // Center on the pixel under the cursor so it lands in the middle of the loupe
+    let (px, py) = (cursor.0.floor() + 0.5, cursor.1.floor() + 0.5);
+
+    ctx.save()?;
+    ctx.arc(cx, cy, radius, 0.0, 2.0 * PI);
+    ctx.clip();
+    ctx.set_source_rgb(0.0, 0.0, 0.0);
+    ctx.paint()?;
+
+    ctx.save()?;
+    ctx.translate(cx, cy);
+    ctx.scale(zoom, zoom);
+    ctx.translate(-px, -py);
+    capture.paint(ctx, transform, size, cairo::Filter::Nearest)?;
+    ctx.restore()?;
+
+    // Pixel grid, one cell per logical pixel
+    ctx.set_source_rgba(0.0, 0.0, 0.0, 0.3);
+    ctx.set_line_width(1.0);
+    let cells = (radius / zoom).ceil() as i32 + 1;
+    for i in -cells..=cells {
+        let d = (i as f64 - 0.5) * zoom;
+        ctx.move_to(cx + d, cy - radius);
+        ctx.line_to(cx + d, cy + radius);
+        ctx.move_to(cx - radius, cy + d);
+        ctx.line_to(cx + radius, cy + d);
+    }
+    ctx.stroke()?;
+
+    set_source_color(ctx, theme.border_color);
+    ctx.rectangle(cx - zoom / 2.0, cy - zoom / 2.0, zoom, zoom);
+    ctx.stroke()?;
+    ctx.restore()?;
+
+    set_source_color(ctx, theme.border_color);
+    ctx.set_line_width(2.0);
+    ctx.arc(cx, cy, radius, 0.0, 2.0 * PI);
+    ctx.stroke()?;
+    Ok(())
+}
+
+fn draw_handles(ctx: &Context, rect: (f64, f64, f64, f64), output_pos: (i32, i32), theme: &Theme) -> Result<(), cairo::Error> {
+    set_source_color(ctx, theme.border_color);
+    for (hx, hy) in handle_points(rect) {
+        let local_x = hx - output_pos.0 as f64;
+        let local_y = hy - output_pos.1 as f64;
+        ctx.rectangle(local_x - HANDLE_SIZE / 2.0, local_y - HANDLE_SIZE / 2.0, HANDLE_SIZE, HANDLE_SIZE);
+        ctx.fill()?;
+    }
+    Ok(())
+}
+
+// A check mark for Accept and a cross for Cancel, on dark squares so they show on any background
+fn draw_confirm_buttons(ctx: &Context, accept: (f64, f64, f64, f64), cancel: (f64, f64, f64, f64), output_pos: (i32, i32)) -> Result<(), cairo::Error> {
+    let inset = BUTTON_SIZE / 4.0;
+    for (x, y, w, h) in [accept, cancel] {
+        ctx.set_source_rgba(0.0, 0.0, 0.0, 0.7);
+        ctx.rectangle(x - output_pos.0 as f64, y - output_pos.1 as f64, w, h);
+        ctx.fill()?;
+    }
+    ctx.set_line_width(2.0);
+
+    let (x, y) = (accept.0 - output_pos.0 as f64, accept.1 - output_pos.1 as f64);
+    ctx.set_source_rgb(0.4, 0.9, 0.4);
+    ctx.move_to(x + inset, y + BUTTON_SIZE / 2.0);
+    ctx.line_to(x + BUTTON_SIZE * 0.45, y + BUTTON_SIZE - inset);
+    ctx.line_to(x + BUTTON_SIZE - inset, y + inset);
+    ctx.stroke()?;
+
+    let (x, y) = (cancel.0 - output_pos.0 as f64, cancel.1 - output_pos.1 as f64);
+    ctx.set_source_rgb(0.9, 0.4, 0.4);
+    ctx.move_to(x + inset, y + inset);
+    ctx.line_to(x + BUTTON_SIZE - inset, y + BUTTON_SIZE - inset);
+    ctx.move_to(x + BUTTON_SIZE - inset, y + inset);
+    ctx.line_to(x + inset, y + BUTTON_SIZE - inset);
+    ctx.stroke()
+}
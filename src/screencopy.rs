@@ -0,0 +1,185 @@
+use std::f64::consts::PI;
+use std::os::unix::io::{AsRawFd, BorrowedFd};
+
+use cairo::{Context, Filter, Format, ImageSurface};
+use memmap2::MmapMut;
+
+use wayland_client::protocol::{
+    wl_buffer::WlBuffer,
+    wl_output::{self, WlOutput},
+    wl_shm,
+    wl_shm_pool::WlShmPool,
+};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum};
+
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+};
+
+use crate::error::Error;
+use crate::State;
+
+// A single frame of an output, captured through wlr-screencopy
+pub struct Capture {
+    pub global_name: u32,
+    frame: ZwlrScreencopyFrameV1,
+    // shm format, width, height and stride offered by the compositor
+    format: Option<(wl_shm::Format, i32, i32, i32)>,
+    buffer: Option<(WlShmPool, WlBuffer, std::fs::File, MmapMut)>,
+    y_invert: bool,
+    pub done: bool,
+    image: Option<ImageSurface>,
+}
+
+impl Capture {
+    pub fn new(manager: &ZwlrScreencopyManagerV1, output: &WlOutput, global_name: u32, qh: &QueueHandle<State>) -> Self {
+        Capture {
+            global_name,
+            frame: manager.capture_output(0, output, qh, global_name),
+            format: None,
+            buffer: None,
+            y_invert: false,
+            done: false,
+            image: None,
+        }
+    }
+
+    fn start_copy(&mut self, shm: &wl_shm::WlShm, qh: &QueueHandle<State>) -> Result<(), Error> {
+        if self.buffer.is_some() {
+            return Ok(());
+        }
+        let Some((format, width, height, stride)) = self.format else {
+            eprintln!("Warning: no supported screencopy buffer format, the output won't be captured");
+            self.finish();
+            return Ok(());
+        };
+
+        let size = stride * height;
+        let file = crate::shm::create(size as usize)?;
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        let pool = shm.create_pool(unsafe { BorrowedFd::borrow_raw(file.as_raw_fd()) }, size, qh, ());
+        let buffer = pool.create_buffer(0, width, height, stride, format, qh, ());
+
+        self.frame.copy(&buffer);
+        self.buffer = Some((pool, buffer, file, mmap));
+        Ok(())
+    }
+
+    // Copies the frame into a cairo surface, the shm buffer isn't needed after that
+    fn store(&mut self) -> Result<(), Error> {
+        if let (Some((format, width, height, stride)), Some((_, _, _, mmap))) = (self.format, self.buffer.as_ref()) {
+            let cairo_format = if format == wl_shm::Format::Argb8888 { Format::ARgb32 } else { Format::Rgb24 };
+            let mut image = ImageSurface::create(cairo_format, width, height)?;
+            let image_stride = image.stride() as usize;
+            let row_len = (width * 4) as usize;
+            {
+                // The surface was just created, nothing else holds a reference to it
+                let mut data = image.data().unwrap();
+                for row in 0..height as usize {
+                    let src = &mmap[row * stride as usize..][..row_len];
+                    data[row * image_stride..][..row_len].copy_from_slice(src);
+                }
+            }
+            self.image = Some(image);
+        }
+        self.finish();
+        Ok(())
+    }
+
+    // A failed capture only loses the frozen background, selecting still works
+    fn check(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result {
+            eprintln!("Warning: failed to capture an output: {}", e);
+            if !self.done {
+                self.finish();
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.frame.destroy();
+        if let Some((pool, buffer, _, _)) = self.buffer.take() {
+            buffer.destroy();
+            pool.destroy();
+        }
+    }
+
+    // Paints the frame over the whole output in logical coordinates, undoing the output transform
+    pub fn paint(&self, ctx: &Context, transform: wl_output::Transform, logical_size: (i32, i32), filter: Filter) -> Result<bool, cairo::Error> {
+        let Some(image) = self.image.as_ref() else {
+            return Ok(false);
+        };
+        let (logical_width, logical_height) = (logical_size.0 as f64, logical_size.1 as f64);
+        let (width, height) = (image.width() as f64, image.height() as f64);
+        let (angle, flipped) = match transform {
+            wl_output::Transform::_90 => (PI / 2.0, false),
+            wl_output::Transform::_180 => (PI, false),
+            wl_output::Transform::_270 => (3.0 * PI / 2.0, false),
+            wl_output::Transform::Flipped => (0.0, true),
+            wl_output::Transform::Flipped90 => (PI / 2.0, true),
+            wl_output::Transform::Flipped180 => (PI, true),
+            wl_output::Transform::Flipped270 => (3.0 * PI / 2.0, true),
+            _ => (0.0, false),
+        };
+        let (target_width, target_height) = if crate::geometry::transform_swaps_axes(transform) { (logical_height, logical_width) } else { (logical_width, logical_height) };
+
+        ctx.save()?;
+        ctx.translate(logical_width / 2.0, logical_height / 2.0);
+        if flipped {
+            ctx.scale(-1.0, 1.0);
+        }
+        ctx.rotate(angle);
+        ctx.scale(target_width / width, target_height / height);
+        if self.y_invert {
+            ctx.scale(1.0, -1.0);
+        }
+        ctx.translate(-width / 2.0, -height / 2.0);
+        ctx.set_source_surface(image, 0.0, 0.0)?;
+        ctx.source().set_filter(filter);
+        ctx.paint()?;
+        ctx.restore()?;
+        Ok(true)
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for State { fn event(_: &mut Self, _: &ZwlrScreencopyManagerV1, _: zwlr_screencopy_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+
+impl Dispatch<ZwlrScreencopyFrameV1, u32> for State {
+    fn event(state: &mut Self, frame: &ZwlrScreencopyFrameV1, event: zwlr_screencopy_frame_v1::Event, global_name: &u32, _: &Connection, qh: &QueueHandle<Self>) {
+        let Some(capture) = state.captures.iter_mut().find(|c| c.global_name == *global_name) else {
+            return;
+        };
+        match event {
+            // Several formats may be offered, cairo can only use these two
+            zwlr_screencopy_frame_v1::Event::Buffer { format: WEnum::Value(format), width, height, stride } => {
+                if capture.format.is_none() && (format == wl_shm::Format::Argb8888 || format == wl_shm::Format::Xrgb8888) {
+                    capture.format = Some((format, width as i32, height as i32, stride as i32));
+                }
+                // Before version 3 there is a single buffer event and no buffer_done
+                if frame.version() < 3 {
+                    let result = capture.start_copy(state.globals.shm.wl_shm(), qh);
+                    capture.check(result);
+                }
+            }
+            zwlr_screencopy_frame_v1::Event::BufferDone => {
+                let result = capture.start_copy(state.globals.shm.wl_shm(), qh);
+                capture.check(result);
+            }
+            zwlr_screencopy_frame_v1::Event::Flags { flags: WEnum::Value(flags) } => {
+                capture.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => {
+                let result = capture.store();
+                capture.check(result);
+            }
+            zwlr_screencopy_frame_v1::Event::Failed => {
+                eprintln!("Warning: failed to capture an output");
+                capture.finish();
+            }
+            _ => {}
+        }
+    }
+}
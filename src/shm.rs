@@ -0,0 +1,19 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+
+// Anonymous memory backing a wl_shm pool. Sealed against shrinking so the compositor can map it
+// without risking SIGBUS, and it never touches the filesystem.
+pub fn create(size: usize) -> io::Result<File> {
+    let name = b"rust-slurp\0";
+    let fd = unsafe { libc::memfd_create(name.as_ptr() as *const libc::c_char, libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let file = unsafe { File::from_raw_fd(fd) };
+    file.set_len(size as u64)?;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, libc::F_SEAL_SHRINK | libc::F_SEAL_SEAL) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
@@ -0,0 +1,70 @@
+use std::io::Read;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1};
+use signal_hook::SigId;
+
+use crate::error::Error;
+
+pub enum Signal {
+    Cancel,
+    Confirm,
+}
+
+// SIGINT and SIGTERM cancel, SIGUSR1 confirms. The handlers only write to a pipe which the event
+// loop polls next to the Wayland connection.
+pub struct Signals {
+    cancel: UnixStream,
+    confirm: UnixStream,
+    ids: Vec<SigId>,
+}
+
+impl Signals {
+    pub fn register() -> Result<Self, Error> {
+        let (cancel, cancel_writer) = UnixStream::pair().map_err(Error::Signals)?;
+        let (confirm, confirm_writer) = UnixStream::pair().map_err(Error::Signals)?;
+        cancel.set_nonblocking(true).map_err(Error::Signals)?;
+        confirm.set_nonblocking(true).map_err(Error::Signals)?;
+        let ids = vec![
+            signal_hook::low_level::pipe::register(SIGINT, cancel_writer.try_clone().map_err(Error::Signals)?).map_err(Error::Signals)?,
+            signal_hook::low_level::pipe::register(SIGTERM, cancel_writer).map_err(Error::Signals)?,
+            signal_hook::low_level::pipe::register(SIGUSR1, confirm_writer).map_err(Error::Signals)?,
+        ];
+        Ok(Signals { cancel, confirm, ids })
+    }
+
+    pub fn fds(&self) -> [RawFd; 2] {
+        [self.cancel.as_raw_fd(), self.confirm.as_raw_fd()]
+    }
+
+    // Cancelling wins when both arrived since the last check
+    pub fn take(&self) -> Option<Signal> {
+        if drain(&self.cancel) {
+            Some(Signal::Cancel)
+        } else if drain(&self.confirm) {
+            Some(Signal::Confirm)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        for id in self.ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+fn drain(mut stream: &UnixStream) -> bool {
+    let mut buf = [0u8; 16];
+    let mut received = false;
+    loop {
+        match stream.read(&mut buf) {
+            Ok(n) if n > 0 => received = true,
+            _ => return received,
+        }
+    }
+}
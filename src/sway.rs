@@ -0,0 +1,81 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use serde_json::Value;
+
+use crate::boxes::CandidateBox;
+use crate::windows::WindowProvider;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const GET_TREE: u32 = 4;
+
+pub struct Sway {
+    socket_path: String,
+}
+
+impl Sway {
+    pub fn from_env() -> Option<Self> {
+        std::env::var("SWAYSOCK").ok().map(|socket_path| Sway { socket_path })
+    }
+
+    fn request(&self, message_type: u32) -> Result<Value, String> {
+        let io_error = |e: std::io::Error| format!("request failed: {}", e);
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(|e| format!("failed to connect to {}: {}", self.socket_path, e))?;
+
+        let mut header = Vec::with_capacity(14);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&0u32.to_ne_bytes());
+        header.extend_from_slice(&message_type.to_ne_bytes());
+        stream.write_all(&header).map_err(io_error)?;
+
+        let mut reply_header = [0u8; 14];
+        stream.read_exact(&mut reply_header).map_err(io_error)?;
+        if &reply_header[..6] != MAGIC {
+            return Err("invalid reply".to_string());
+        }
+        let len = u32::from_ne_bytes([reply_header[6], reply_header[7], reply_header[8], reply_header[9]]) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).map_err(io_error)?;
+
+        serde_json::from_slice(&payload).map_err(|e| format!("invalid JSON: {}", e))
+    }
+}
+
+impl WindowProvider for Sway {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn windows(&self) -> Result<Vec<CandidateBox>, String> {
+        let tree = self.request(GET_TREE)?;
+        let mut boxes = Vec::new();
+        collect_windows(&tree, &mut boxes);
+        Ok(boxes)
+    }
+}
+
+// Windows are the containers with a client pid, both tiled and floating
+fn collect_windows(node: &Value, boxes: &mut Vec<CandidateBox>) {
+    if node["pid"].is_number() && node["visible"].as_bool() == Some(true) {
+        let rect = &node["rect"];
+        if let (Some(x), Some(y), Some(w), Some(h)) = (rect["x"].as_f64(), rect["y"].as_f64(), rect["width"].as_f64(), rect["height"].as_f64()) {
+            if w > 0.0 && h > 0.0 {
+                // Xwayland windows have no app_id, use their X11 class instead
+                let app_id = node["app_id"].as_str().or_else(|| node["window_properties"]["class"].as_str());
+                boxes.push(CandidateBox {
+                    rect: (x, y, w, h),
+                    label: None,
+                    app_id: app_id.map(str::to_string),
+                    title: node["name"].as_str().map(str::to_string),
+                });
+            }
+        }
+    }
+    for key in ["nodes", "floating_nodes"] {
+        if let Some(children) = node[key].as_array() {
+            for child in children {
+                collect_windows(child, boxes);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use cairo::Context;
+use pango::{FontDescription, Layout};
+
+pub const DEFAULT_FONT: &str = "sans 10";
+
+const PADDING: f64 = 4.0;
+const OFFSET: f64 = 8.0;
+
+// All text on the overlay goes through pango so it uses the --font setting
+fn layout(ctx: &Context, text: &str, font: &FontDescription) -> Layout {
+    let layout = pangocairo::functions::create_layout(ctx);
+    layout.set_font_description(Some(font));
+    layout.set_text(text);
+    layout
+}
+
+fn draw_boxed(ctx: &Context, layout: &Layout, (x, y): (f64, f64), (width, height): (f64, f64)) -> Result<(), cairo::Error> {
+    ctx.set_source_rgba(0.0, 0.0, 0.0, 0.7);
+    ctx.rectangle(x, y, width, height);
+    ctx.fill()?;
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.move_to(x + PADDING, y + PADDING);
+    pangocairo::functions::show_layout(ctx, layout);
+    Ok(())
+}
+
+fn boxed_size(layout: &Layout) -> (f64, f64) {
+    let (width, height) = layout.pixel_size();
+    (width as f64 + 2.0 * PADDING, height as f64 + 2.0 * PADDING)
+}
+
+// Text in a small box next to a point, on the side given by direction unless that would leave the output
+pub fn draw_label(ctx: &Context, text: &str, font: &FontDescription, point: (f64, f64), direction: (f64, f64), size: (i32, i32)) -> Result<(), cairo::Error> {
+    let layout = layout(ctx, text, font);
+    let (width, height) = boxed_size(&layout);
+
+    let place = |p: f64, d: f64, len: f64, max: f64| {
+        let after = p + OFFSET;
+        let before = p - OFFSET - len;
+        let start = if (d >= 0.0 && after + len <= max) || (d < 0.0 && before < 0.0) { after } else { before };
+        start.clamp(0.0, (max - len).max(0.0))
+    };
+    let x = place(point.0, direction.0, width, size.0 as f64);
+    let y = place(point.1, direction.1, height, size.1 as f64);
+
+    draw_boxed(ctx, &layout, (x, y), (width, height))
+}
+
+// Text in a small box inside the top left corner of a rectangle
+pub fn draw_tag(ctx: &Context, text: &str, font: &FontDescription, corner: (f64, f64)) -> Result<(), cairo::Error> {
+    let layout = layout(ctx, text, font);
+    let size = boxed_size(&layout);
+    draw_boxed(ctx, &layout, corner, size)
+}
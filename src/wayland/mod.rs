@@ -0,0 +1,7 @@
+mod outputs;
+mod registry;
+mod seat;
+
+pub(crate) use outputs::OutputState;
+pub(crate) use registry::Globals;
+pub(crate) use seat::Seat;
@@ -0,0 +1,485 @@
+use wayland_client::protocol::{
+    wl_buffer::{self, WlBuffer},
+    wl_callback::{self, WlCallback},
+    wl_output::{self, WlOutput},
+    wl_shm_pool::{self, WlShmPool},
+    wl_surface,
+};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+
+use smithay_client_toolkit::delegate_output;
+use smithay_client_toolkit::output::{OutputHandler, OutputState as OutputRegistry};
+
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1,
+    zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::{self, XdgSurface},
+    xdg_toplevel::{self, XdgToplevel},
+};
+use wayland_protocols::wp::fractional_scale::v1::client::wp_fractional_scale_v1::{self, WpFractionalScaleV1};
+use wayland_protocols::wp::keyboard_shortcuts_inhibit::zv1::client::zwp_keyboard_shortcuts_inhibitor_v1::ZwpKeyboardShortcutsInhibitorV1;
+use wayland_protocols::wp::viewporter::client::wp_viewport::{self, WpViewport};
+
+use tracing::debug;
+
+use crate::geometry::{intersect_rect, transform_swaps_axes};
+use crate::render::RenderTarget;
+use crate::{Error, Rect, State};
+
+pub(crate) struct OutputState {
+    pub global_name: u32,
+    pub output: WlOutput,
+    pub logical_pos: (i32, i32),
+    pub logical_size: (i32, i32),
+    pub size: (u32, u32),
+    pub transform: wl_output::Transform,
+    // Logical size from the last shell surface configure
+    pub configured_size: (u32, u32),
+    // Nothing may be attached before the first configure was acked
+    pub configured: bool,
+    pub scale: i32,
+    // Preferred scale from wp_fractional_scale_v1, rendered through a wp_viewport
+    pub fractional_scale: Option<WpFractionalScaleV1>,
+    pub viewport: Option<WpViewport>,
+    pub preferred_scale: Option<f64>,
+    pub name: Option<String>,
+    // Created on the first keyboard enter with --inhibit-shortcuts
+    pub shortcuts_inhibitor: Option<ZwpKeyboardShortcutsInhibitorV1>,
+    pub surface: wl_surface::WlSurface,
+    pub shell_surface: ShellSurface,
+    pub target: Option<Box<dyn RenderTarget>>,
+    // A frame callback is outstanding, nothing is drawn until it's done
+    pub frame_pending: bool,
+    pub redraw_pending: bool,
+    // What State::dynamic_regions returned for the last frame
+    pub drawn_regions: Vec<Rect>,
+    // Mirrored outputs showing the same logical area, they get no overlay of their own
+    pub mirrors: Vec<(u32, WlOutput)>,
+}
+
+pub(crate) enum ShellSurface {
+    Layer(ZwlrLayerSurfaceV1),
+    Toplevel(XdgSurface, XdgToplevel),
+}
+
+impl ShellSurface {
+    fn destroy(&self) {
+        match self {
+            ShellSurface::Layer(layer_surface) => layer_surface.destroy(),
+            ShellSurface::Toplevel(xdg_surface, toplevel) => {
+                toplevel.destroy();
+                xdg_surface.destroy();
+            }
+        }
+    }
+}
+
+impl OutputState {
+    // Whether a frame with these dynamic regions would look any different from the last one
+    pub fn affected_by(&self, regions: &[Rect]) -> bool {
+        let rect = self.logical_rect();
+        let invalid = self.target.as_ref().map_or(true, |t| t.invalid());
+        invalid || self.drawn_regions.iter().chain(regions).any(|&r| intersect_rect(r, rect).is_some())
+    }
+
+    // The next frames get drawn in full, for changes that aren't tracked as regions
+    pub fn invalidate(&mut self) {
+        if let Some(target) = self.target.as_mut() {
+            target.invalidate();
+        }
+    }
+
+    // Fullscreen toplevels may be configured without a size, which leaves it to the output's size
+    pub fn fill_unconfigured_size(&mut self) {
+        if self.configured && matches!(self.shell_surface, ShellSurface::Toplevel(..)) && (self.configured_size.0 == 0 || self.configured_size.1 == 0) {
+            let (width, height) = self.effective_logical_size();
+            self.configured_size = (width.max(0) as u32, height.max(0) as u32);
+        }
+    }
+
+    // xdg-output reports the logical size with transform and scale already applied. Without it,
+    // derive the size from the mode, swapping the axes of outputs rotated by 90 or 270 degrees.
+    pub fn effective_logical_size(&self) -> (i32, i32) {
+        if self.logical_size.0 > 0 && self.logical_size.1 > 0 {
+            return self.logical_size;
+        }
+        let (w, h) = (self.size.0 as i32 / self.scale, self.size.1 as i32 / self.scale);
+        if transform_swaps_axes(self.transform) { (h, w) } else { (w, h) }
+    }
+
+    // Buffer size in pixels, None until the surface was configured
+    pub fn buffer_size(&self) -> Option<(i32, i32)> {
+        let (logical_width, logical_height) = self.configured_size;
+        if logical_width == 0 || logical_height == 0 {
+            return None;
+        }
+        Some(match self.preferred_scale {
+            Some(scale) => ((logical_width as f64 * scale).round() as i32, (logical_height as f64 * scale).round() as i32),
+            None => (logical_width as i32 * self.scale, logical_height as i32 * self.scale),
+        })
+    }
+
+    pub fn logical_rect(&self) -> (f64, f64, f64, f64) {
+        let (w, h) = self.effective_logical_size();
+        (self.logical_pos.0 as f64, self.logical_pos.1 as f64, w as f64, h as f64)
+    }
+}
+
+impl State {
+    // Fractional scaling needs both protocols, otherwise the integer buffer scale is used
+    fn setup_fractional_scale(&mut self, output_index: usize) {
+        let (Some(manager), Some(viewporter)) = (self.globals.fractional_scale_manager.as_ref(), self.globals.viewporter.as_ref()) else {
+            return;
+        };
+        let output_state = &mut self.outputs[output_index];
+        if output_state.fractional_scale.is_none() {
+            output_state.fractional_scale = Some(manager.get_fractional_scale(&output_state.surface, &self.qh, ()));
+            output_state.viewport = Some(viewporter.get_viewport(&output_state.surface, &self.qh, ()));
+        }
+    }
+
+    pub(crate) fn add_output(&mut self, global_name: u32, output: WlOutput) {
+        if !self.options.outputs.is_empty() {
+            let name = self.output_registry.info(&output).and_then(|info| info.name);
+            if !name.as_ref().is_some_and(|name| self.options.outputs.contains(name)) {
+                debug!(global_name, ?name, "output left out by --outputs");
+                return;
+            }
+        }
+        // Overlapping overlays would both take input, a mirror is left to the output it copies
+        if let Some(primary) = self.mirrored_output(&output) {
+            debug!(global_name, primary = ?self.outputs[primary].name, "output mirrors another one");
+            self.outputs[primary].mirrors.push((global_name, output));
+            return;
+        }
+        debug!(global_name, layer_shell = self.globals.layer_shell.is_some(), "adding output");
+        let qh = &self.qh;
+        let surface = self.globals.compositor.create_surface(qh, ());
+        let shell_surface = match (&self.globals.layer_shell, &self.globals.wm_base) {
+            (Some(layer_shell), _) => {
+                let layer_surface = layer_shell.get_layer_surface(&surface, Some(&output), zwlr_layer_shell_v1::Layer::Overlay, "rust-slurp".to_string(), qh, ());
+                layer_surface.set_anchor(zwlr_layer_surface_v1::Anchor::Top | zwlr_layer_surface_v1::Anchor::Right | zwlr_layer_surface_v1::Anchor::Bottom | zwlr_layer_surface_v1::Anchor::Left);
+                // OnDemand needs version 4, older versions only know None and Exclusive, so they always grab the keyboard
+                let interactivity = if self.options.grab_keyboard || layer_surface.version() < 4 {
+                    zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive
+                } else {
+                    zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand
+                };
+                layer_surface.set_keyboard_interactivity(interactivity);
+                layer_surface.set_exclusive_zone(-1);
+                ShellSurface::Layer(layer_surface)
+            }
+            // Most compositors hide what's below a fullscreen window, --freeze keeps the screen contents visible
+            (None, Some(wm_base)) => {
+                let xdg_surface = wm_base.get_xdg_surface(&surface, qh, ());
+                let toplevel = xdg_surface.get_toplevel(qh, ());
+                toplevel.set_title("rust-slurp".to_string());
+                toplevel.set_app_id("rust-slurp".to_string());
+                toplevel.set_fullscreen(Some(&output));
+                ShellSurface::Toplevel(xdg_surface, toplevel)
+            }
+            // Setup fails without either shell, should one still be missing the output is left without an overlay
+            (None, None) => {
+                eprintln!("Warning: no shell to show an overlay on output {} with, skipping it", global_name);
+                surface.destroy();
+                return;
+            }
+        };
+        if !self.hidden {
+            surface.commit();
+        }
+
+        self.outputs.push(OutputState {
+            global_name,
+            output,
+            logical_pos: (0, 0),
+            logical_size: (0, 0),
+            size: (0, 0),
+            transform: wl_output::Transform::Normal,
+            configured_size: (0, 0),
+            configured: false,
+            scale: 1,
+            fractional_scale: None,
+            viewport: None,
+            preferred_scale: None,
+            name: None,
+            shortcuts_inhibitor: None,
+            surface,
+            shell_surface,
+            target: None,
+            frame_pending: false,
+            redraw_pending: false,
+            drawn_regions: Vec::new(),
+            mirrors: Vec::new(),
+        });
+        self.setup_fractional_scale(self.outputs.len() - 1);
+        self.update_output_info(self.outputs.len() - 1);
+    }
+
+    // An output already shown with the same logical geometry
+    fn mirrored_output(&self, output: &WlOutput) -> Option<usize> {
+        let info = self.output_registry.info(output)?;
+        let (pos, size) = (info.logical_position?, info.logical_size?);
+        self.outputs.iter().position(|o| o.logical_pos == pos && o.logical_size == size)
+    }
+
+    // An output moved onto another one becomes its mirror, and mirrors whose geometry no longer matches get an
+    // overlay of their own. True when overlays were added or removed.
+    fn update_mirrors(&mut self, output: &WlOutput) -> bool {
+        let mut changed = false;
+        if let Some(index) = self.outputs.iter().position(|o| o.output == *output) {
+            let (pos, size) = (self.outputs[index].logical_pos, self.outputs[index].logical_size);
+            if self.outputs.iter().enumerate().any(|(i, o)| i != index && o.logical_pos == pos && o.logical_size == size) {
+                let global_name = self.outputs[index].global_name;
+                self.remove_output(index);
+                self.add_output(global_name, output.clone());
+                changed = true;
+            }
+        }
+        let mut separated = Vec::new();
+        for output_state in &mut self.outputs {
+            let (pos, size) = (output_state.logical_pos, output_state.logical_size);
+            let registry = &self.output_registry;
+            output_state.mirrors.retain(|(global_name, mirror)| {
+                let mirrors = registry.info(mirror).is_some_and(|info| info.logical_position == Some(pos) && info.logical_size == Some(size));
+                if !mirrors {
+                    separated.push((*global_name, mirror.clone()));
+                }
+                mirrors
+            });
+        }
+        for (global_name, mirror) in separated {
+            debug!(global_name, "output stopped mirroring");
+            self.add_output(global_name, mirror);
+            changed = true;
+        }
+        changed
+    }
+
+    // Copies what the compositor announced about the output, the logical geometry comes from xdg-output
+    fn update_output_info(&mut self, index: usize) {
+        let output_state = &mut self.outputs[index];
+        let Some(info) = self.output_registry.info(&output_state.output) else {
+            return;
+        };
+        output_state.logical_pos = info.logical_position.unwrap_or((0, 0));
+        output_state.logical_size = info.logical_size.unwrap_or((0, 0));
+        // Older outputs advertise every mode, only the current one matters
+        output_state.size = info.modes.iter().find(|m| m.current).map_or((0, 0), |m| (m.dimensions.0 as u32, m.dimensions.1 as u32));
+        output_state.transform = info.transform;
+        output_state.scale = info.scale_factor.max(1);
+        output_state.name = info.name;
+        debug!(
+            output = ?output_state.name,
+            logical_pos = ?output_state.logical_pos,
+            logical_size = ?output_state.logical_size,
+            size = ?output_state.size,
+            scale = output_state.scale,
+            transform = ?output_state.transform,
+            "output info"
+        );
+    }
+
+    // Tears down an output's surfaces and keeps the output indices held elsewhere valid
+    fn remove_output(&mut self, index: usize) {
+        let mut output_state = self.outputs.remove(index);
+        debug!(output = ?output_state.name, "removing output");
+        let mirrors = std::mem::take(&mut output_state.mirrors);
+        if let Some(fractional_scale) = output_state.fractional_scale {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = output_state.viewport {
+            viewport.destroy();
+        }
+        if let Some(inhibitor) = output_state.shortcuts_inhibitor {
+            inhibitor.destroy();
+        }
+        if let Some(mut target) = output_state.target {
+            target.destroy();
+        }
+        output_state.shell_surface.destroy();
+        output_state.surface.destroy();
+
+        self.current_output = match self.current_output {
+            Some(i) if i == index => None,
+            Some(i) if i > index => Some(i - 1),
+            other => other,
+        };
+        self.touch_point = match self.touch_point {
+            Some((_, i)) if i == index => None,
+            Some((id, i)) if i > index => Some((id, i - 1)),
+            other => other,
+        };
+        self.tool_output = match self.tool_output {
+            Some(i) if i == index => None,
+            Some(i) if i > index => Some(i - 1),
+            other => other,
+        };
+        if self.focused_output > index || self.focused_output >= self.outputs.len() {
+            self.focused_output = self.focused_output.saturating_sub(1);
+        }
+
+        // The first mirror takes over the overlay, the others mirror it in turn
+        for (global_name, output) in mirrors {
+            self.add_output(global_name, output);
+        }
+
+        // Nothing left to select on
+        if self.outputs.is_empty() {
+            self.fail(Error::NoOutputs);
+        }
+    }
+}
+
+impl OutputHandler for State {
+    fn output_state(&mut self) -> &mut OutputRegistry {
+        &mut self.output_registry
+    }
+
+    // Outputs can show up before the frozen frames were captured, and at any time later when hot-plugged
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, output: WlOutput) {
+        let Some(info) = self.output_registry.info(&output) else {
+            return;
+        };
+        if self.setup_done {
+            self.add_output(info.id, output);
+        } else {
+            self.pending_outputs.push((info.id, output));
+        }
+    }
+
+    // The scale may change after the surface was configured, and moved outputs may start or stop mirroring
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, output: WlOutput) {
+        if let Some(index) = self.outputs.iter().position(|o| o.output == output) {
+            self.update_output_info(index);
+            self.outputs[index].fill_unconfigured_size();
+            self.draw_on_output(index);
+        }
+        if self.setup_done && self.update_mirrors(&output) {
+            self.draw();
+        }
+    }
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, output: WlOutput) {
+        for output_state in &mut self.outputs {
+            output_state.mirrors.retain(|(_, o)| *o != output);
+        }
+        if let Some(index) = self.outputs.iter().position(|o| o.output == output) {
+            self.remove_output(index);
+            self.draw();
+        } else {
+            self.pending_outputs.retain(|(_, o)| *o != output);
+        }
+    }
+}
+
+delegate_output!(State);
+
+impl Dispatch<wl_surface::WlSurface, ()> for State { fn event(_: &mut Self, _: &wl_surface::WlSurface, _: wl_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, width, height } => {
+                surface.ack_configure(serial);
+                if let Some(output_index) = state.outputs.iter().position(|o| matches!(&o.shell_surface, ShellSurface::Layer(l) if l.id() == surface.id())) {
+                    debug!(output = ?state.outputs[output_index].name, width, height, "layer surface configured");
+                    state.outputs[output_index].configured_size = (width, height);
+                    state.outputs[output_index].configured = true;
+                    state.draw_on_output(output_index);
+                }
+            }
+            // Sent when the output goes away, the rest of the outputs stay usable
+            zwlr_layer_surface_v1::Event::Closed => {
+                if let Some(output_index) = state.outputs.iter().position(|o| matches!(&o.shell_surface, ShellSurface::Layer(l) if l.id() == surface.id())) {
+                    state.remove_output(output_index);
+                    state.draw();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for State {
+    fn event(state: &mut Self, xdg_surface: &XdgSurface, event: xdg_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+            if let Some(output_index) = state.outputs.iter().position(|o| matches!(&o.shell_surface, ShellSurface::Toplevel(s, _) if s.id() == xdg_surface.id())) {
+                debug!(output = ?state.outputs[output_index].name, size = ?state.outputs[output_index].configured_size, "xdg surface configured");
+                state.outputs[output_index].configured = true;
+                state.outputs[output_index].fill_unconfigured_size();
+                state.draw_on_output(output_index);
+            }
+        }
+    }
+}
+
+impl Dispatch<XdgToplevel, ()> for State {
+    fn event(state: &mut Self, toplevel: &XdgToplevel, event: xdg_toplevel::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            xdg_toplevel::Event::Configure { width, height, .. } => {
+                if let Some(output_state) = state.outputs.iter_mut().find(|o| matches!(&o.shell_surface, ShellSurface::Toplevel(_, t) if t.id() == toplevel.id())) {
+                    output_state.configured_size = (width.max(0) as u32, height.max(0) as u32);
+                }
+            }
+            xdg_toplevel::Event::Close => {
+                state.running = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WpViewport, ()> for State { fn event(_: &mut Self, _: &WpViewport, _: wp_viewport::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+
+impl Dispatch<WpFractionalScaleV1, ()> for State {
+    fn event(state: &mut Self, fractional_scale: &WpFractionalScaleV1, event: wp_fractional_scale_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wp_fractional_scale_v1::Event::PreferredScale { scale } = event {
+            if let Some(index) = state.outputs.iter().position(|o| o.fractional_scale.as_ref().is_some_and(|f| f.id() == fractional_scale.id())) {
+                // The scale is sent in 120ths
+                debug!(output = ?state.outputs[index].name, scale = scale as f64 / 120.0, "preferred scale");
+                state.outputs[index].preferred_scale = Some(scale as f64 / 120.0);
+                state.draw_on_output(index);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for State { fn event(_: &mut Self, _: &WlShmPool, _: wl_shm_pool::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<WlCallback, u32> for State {
+    fn event(state: &mut Self, _: &WlCallback, event: wl_callback::Event, global_name: &u32, _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_callback::Event::Done { .. } = event {
+            let Some(output_index) = state.outputs.iter().position(|o| o.global_name == *global_name) else {
+                return;
+            };
+            state.outputs[output_index].frame_pending = false;
+            if state.outputs[output_index].redraw_pending {
+                state.draw_on_output(output_index);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for State {
+    fn event(state: &mut Self, buffer: &WlBuffer, event: wl_buffer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_buffer::Event::Release = event {
+            // Screencopy buffers aren't any output's
+            let Some(output_index) = state.outputs.iter_mut().position(|o| o.target.as_mut().is_some_and(|t| t.release(buffer))) else {
+                return;
+            };
+            if state.outputs[output_index].redraw_pending {
+                state.draw_on_output(output_index);
+            }
+        }
+    }
+}
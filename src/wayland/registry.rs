@@ -0,0 +1,110 @@
+use wayland_client::globals::GlobalList;
+use wayland_client::protocol::wl_compositor::{self, WlCompositor};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+
+use smithay_client_toolkit::output::OutputState as OutputRegistry;
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::seat::SeatState;
+use smithay_client_toolkit::shm::{Shm, ShmHandler};
+use smithay_client_toolkit::{delegate_registry, delegate_shm, registry_handlers};
+
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::{self, ZwlrLayerShellV1};
+use wayland_protocols_wlr::screencopy::v1::client::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1;
+use wayland_protocols::xdg::shell::client::xdg_wm_base::{self, XdgWmBase};
+use wayland_protocols::wp::fractional_scale::v1::client::wp_fractional_scale_manager_v1::{self, WpFractionalScaleManagerV1};
+use wayland_protocols::wp::keyboard_shortcuts_inhibit::zv1::client::zwp_keyboard_shortcuts_inhibit_manager_v1::{self, ZwpKeyboardShortcutsInhibitManagerV1};
+use wayland_protocols::wp::tablet::zv2::client::zwp_tablet_manager_v2::{self, ZwpTabletManagerV2};
+use wayland_protocols::wp::viewporter::client::wp_viewporter::{self, WpViewporter};
+
+use tracing::debug;
+
+use crate::{Error, State};
+
+// Globals bound once at startup, the optional ones are None on compositors without them
+pub(crate) struct Globals {
+    pub registry_state: RegistryState,
+    pub compositor: WlCompositor,
+    pub shm: Shm,
+    pub layer_shell: Option<ZwlrLayerShellV1>,
+    pub wm_base: Option<XdgWmBase>,
+    pub fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    pub viewporter: Option<WpViewporter>,
+    pub tablet_manager: Option<ZwpTabletManagerV2>,
+    pub screencopy_manager: Option<ZwlrScreencopyManagerV1>,
+    pub shortcuts_inhibit_manager: Option<ZwpKeyboardShortcutsInhibitManagerV1>,
+}
+
+impl Globals {
+    // Fails with the names of the required protocols the compositor lacks
+    pub fn bind(globals: &GlobalList, qh: &QueueHandle<State>) -> Result<Self, Error> {
+        let compositor = globals.bind(qh, 4..=6, ()).ok();
+        let shm = Shm::bind(globals, qh).ok();
+        let layer_shell = globals.bind(qh, 1..=4, ()).ok();
+        let wm_base = globals.bind(qh, 1..=6, ()).ok();
+        // Seats and xdg-output are bound by sctk, only their presence is checked here
+        globals.contents().with_list(|list| {
+            for global in list {
+                debug!(interface = %global.interface, version = global.version, name = global.name, "global");
+            }
+        });
+        let has_global = |interface: &str| globals.contents().with_list(|list| list.iter().any(|g| g.interface == interface));
+
+        let missing: Vec<&str> = [
+            ("wl_compositor", compositor.is_none()),
+            ("wl_shm", shm.is_none()),
+            ("zwlr_layer_shell_v1 or xdg_wm_base", layer_shell.is_none() && wm_base.is_none()),
+            ("wl_seat", !has_global("wl_seat")),
+            ("zxdg_output_manager_v1", !has_global("zxdg_output_manager_v1")),
+        ]
+        .into_iter()
+        .filter_map(|(name, is_missing)| is_missing.then_some(name))
+        .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingProtocols(missing.join(" ")));
+        }
+
+        Ok(Globals {
+            registry_state: RegistryState::new(globals),
+            compositor: compositor.unwrap(),
+            shm: shm.unwrap(),
+            layer_shell,
+            wm_base,
+            fractional_scale_manager: globals.bind(qh, 1..=1, ()).ok(),
+            viewporter: globals.bind(qh, 1..=1, ()).ok(),
+            tablet_manager: globals.bind(qh, 1..=1, ()).ok(),
+            screencopy_manager: globals.bind(qh, 1..=3, ()).ok(),
+            shortcuts_inhibit_manager: globals.bind(qh, 1..=1, ()).ok(),
+        })
+    }
+}
+
+impl Dispatch<WlCompositor, ()> for State { fn event(_: &mut Self, _: &WlCompositor, _: wl_compositor::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<ZwlrLayerShellV1, ()> for State { fn event(_: &mut Self, _: &ZwlrLayerShellV1, _: zwlr_layer_shell_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<WpFractionalScaleManagerV1, ()> for State { fn event(_: &mut Self, _: &WpFractionalScaleManagerV1, _: wp_fractional_scale_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<WpViewporter, ()> for State { fn event(_: &mut Self, _: &WpViewporter, _: wp_viewporter::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<ZwpTabletManagerV2, ()> for State { fn event(_: &mut Self, _: &ZwpTabletManagerV2, _: zwp_tablet_manager_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<ZwpKeyboardShortcutsInhibitManagerV1, ()> for State { fn event(_: &mut Self, _: &ZwpKeyboardShortcutsInhibitManagerV1, _: zwp_keyboard_shortcuts_inhibit_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+
+impl ProvidesRegistryState for State {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.globals.registry_state
+    }
+    registry_handlers![OutputRegistry, SeatState];
+}
+
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.globals.shm
+    }
+}
+
+delegate_registry!(State);
+delegate_shm!(State);
+
+impl Dispatch<XdgWmBase, ()> for State {
+    fn event(_: &mut Self, wm_base: &XdgWmBase, event: xdg_wm_base::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use wayland_client::globals::GlobalList;
+use wayland_client::protocol::{
+    wl_keyboard,
+    wl_pointer::{self, WlPointer},
+    wl_seat::WlSeat,
+    wl_touch::{self, WlTouch},
+};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum};
+
+use smithay_client_toolkit::delegate_seat;
+use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
+
+use wayland_protocols::wp::keyboard_shortcuts_inhibit::zv1::client::zwp_keyboard_shortcuts_inhibitor_v1::{self, ZwpKeyboardShortcutsInhibitorV1};
+use wayland_protocols::wp::tablet::zv2::client::{
+    zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+    zwp_tablet_pad_ring_v2::{self, ZwpTabletPadRingV2},
+    zwp_tablet_pad_strip_v2::{self, ZwpTabletPadStripV2},
+    zwp_tablet_pad_v2::{self, ZwpTabletPadV2},
+    zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+    zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+    zwp_tablet_v2::{self, ZwpTabletV2},
+};
+
+use tracing::{debug, trace};
+use xkbcommon::xkb;
+
+use crate::geometry::to_global;
+use crate::State;
+
+// Linux evdev codes, as sent in wl_keyboard.key
+const KEY_ESC: u32 = 1;
+const KEY_ENTER: u32 = 28;
+
+// The seat the selection is made with and its input devices, only the first seat is used
+pub(crate) struct Seat {
+    pub state: SeatState,
+    pub wl_seat: Option<WlSeat>,
+    pub pointer: Option<WlPointer>,
+    pub keyboard: Option<wl_keyboard::WlKeyboard>,
+    pub touch: Option<WlTouch>,
+    pub tablet_seat: Option<ZwpTabletSeatV2>,
+    pub xkb_context: xkb::Context,
+    pub xkb_state: Option<xkb::State>,
+}
+
+impl Seat {
+    pub fn new(globals: &GlobalList, qh: &QueueHandle<State>) -> Self {
+        Seat {
+            state: SeatState::new(globals, qh),
+            wl_seat: None,
+            pointer: None,
+            keyboard: None,
+            touch: None,
+            tablet_seat: None,
+            xkb_context: xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+            xkb_state: None,
+        }
+    }
+}
+
+impl State {
+    // Input devices die with their seat, release them so no events arrive for stale proxies
+    fn release_seat(&mut self) {
+        if let Some(pointer) = self.seat.pointer.take() {
+            if pointer.version() >= 3 {
+                pointer.release();
+            }
+        }
+        if let Some(keyboard) = self.seat.keyboard.take() {
+            if keyboard.version() >= 3 {
+                keyboard.release();
+            }
+        }
+        if let Some(touch) = self.seat.touch.take() {
+            if touch.version() >= 3 {
+                touch.release();
+            }
+        }
+        self.touch_point = None;
+        if let Some(tablet_seat) = self.seat.tablet_seat.take() {
+            tablet_seat.destroy();
+        }
+        self.tool_output = None;
+        // Inhibitors belong to the seat they were created for
+        for output_state in &mut self.outputs {
+            if let Some(inhibitor) = output_state.shortcuts_inhibitor.take() {
+                inhibitor.destroy();
+            }
+        }
+        self.seat.wl_seat = None;
+        self.seat.xkb_state = None;
+        self.start_pos = None;
+        self.button_held = false;
+    }
+
+    // Pens bypass wl_pointer on many compositors, their events come through the tablet seat instead
+    fn bind_tablet_seat(&mut self) {
+        if let (Some(manager), Some(seat), None) = (&self.globals.tablet_manager, &self.seat.wl_seat, &self.seat.tablet_seat) {
+            self.seat.tablet_seat = Some(manager.get_tablet_seat(seat, &self.qh, ()));
+        }
+    }
+
+    // Compositors only honour the inhibitor while the surface has keyboard focus, so one per output is enough
+    fn inhibit_shortcuts(&mut self, index: usize) {
+        if !self.options.inhibit_shortcuts {
+            return;
+        }
+        let output_state = &mut self.outputs[index];
+        if let (Some(manager), Some(seat), None) = (&self.globals.shortcuts_inhibit_manager, &self.seat.wl_seat, &output_state.shortcuts_inhibitor) {
+            output_state.shortcuts_inhibitor = Some(manager.inhibit_shortcuts(&output_state.surface, seat, &self.qh, ()));
+        }
+    }
+}
+
+impl SeatHandler for State {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat.state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat) {
+        if self.seat.wl_seat.is_none() {
+            self.seat.wl_seat = Some(seat);
+            self.bind_tablet_seat();
+        }
+    }
+
+    fn new_capability(&mut self, conn: &Connection, qh: &QueueHandle<Self>, seat: WlSeat, capability: Capability) {
+        self.new_seat(conn, qh, seat.clone());
+        if self.seat.wl_seat.as_ref() != Some(&seat) {
+            return;
+        }
+        debug!(?capability, "new seat capability");
+        match capability {
+            Capability::Pointer if self.seat.pointer.is_none() => self.seat.pointer = Some(seat.get_pointer(qh, ())),
+            Capability::Keyboard if self.seat.keyboard.is_none() => self.seat.keyboard = Some(seat.get_keyboard(qh, ())),
+            Capability::Touch if self.seat.touch.is_none() => self.seat.touch = Some(seat.get_touch(qh, ())),
+            _ => {}
+        }
+    }
+
+    fn remove_capability(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat, capability: Capability) {
+        if self.seat.wl_seat.as_ref() != Some(&seat) {
+            return;
+        }
+        debug!(?capability, "seat capability removed");
+        match capability {
+            Capability::Pointer => {
+                if let Some(pointer) = self.seat.pointer.take() {
+                    if pointer.version() >= 3 {
+                        pointer.release();
+                    }
+                }
+                self.start_pos = None;
+                self.button_held = false;
+            }
+            Capability::Keyboard => {
+                if let Some(keyboard) = self.seat.keyboard.take() {
+                    if keyboard.version() >= 3 {
+                        keyboard.release();
+                    }
+                }
+                self.seat.xkb_state = None;
+            }
+            Capability::Touch => {
+                if let Some(touch) = self.seat.touch.take() {
+                    if touch.version() >= 3 {
+                        touch.release();
+                    }
+                }
+                self.touch_point = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat) {
+        if self.seat.wl_seat.as_ref() == Some(&seat) {
+            self.release_seat();
+        }
+    }
+}
+
+delegate_seat!(State);
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
+    fn event(state: &mut Self, _: &wl_keyboard::WlKeyboard, event: wl_keyboard::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            wl_keyboard::Event::Enter { surface, .. } => {
+                if let Some(index) = state.outputs.iter().position(|o| o.surface.id() == surface.id()) {
+                    state.inhibit_shortcuts(index);
+                }
+            }
+            wl_keyboard::Event::Keymap { format, fd, size } => {
+                if format != WEnum::Value(wl_keyboard::KeymapFormat::XkbV1) {
+                    return;
+                }
+                let keymap = unsafe { xkb::Keymap::new_from_fd(&state.seat.xkb_context, fd, size as usize, xkb::KEYMAP_FORMAT_TEXT_V1, xkb::KEYMAP_COMPILE_NO_FLAGS) };
+                match keymap {
+                    Ok(Some(keymap)) => state.seat.xkb_state = Some(xkb::State::new(&keymap)),
+                    _ => eprintln!("Warning: failed to load the keyboard keymap"),
+                }
+            }
+            wl_keyboard::Event::Modifiers { mods_depressed, mods_latched, mods_locked, group, .. } => {
+                if let Some(xkb_state) = state.seat.xkb_state.as_mut() {
+                    xkb_state.update_mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
+                }
+                // Modifiers change the shape of an in-progress selection
+                if state.start_pos.is_some() {
+                    state.draw();
+                }
+            }
+            wl_keyboard::Event::Key { key, state: key_state, .. } => {
+                if key_state != WEnum::Value(wl_keyboard::KeyState::Pressed) {
+                    return;
+                }
+                // Wayland keycodes are evdev codes, xkb keycodes are offset by 8. Without a keymap only
+                // Escape and Enter are known, so the selection can still be cancelled or confirmed.
+                let keysym = match state.seat.xkb_state.as_ref() {
+                    Some(xkb_state) => xkb_state.key_get_one_sym(xkb::Keycode::new(key + 8)),
+                    None => match key {
+                        KEY_ESC => xkb::Keysym::Escape,
+                        KEY_ENTER => xkb::Keysym::Return,
+                        _ => return,
+                    },
+                };
+                trace!(key, ?keysym, "key pressed");
+                state.handle_key(keysym);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for State {
+    fn event(state: &mut Self, pointer: &WlPointer, event: wl_pointer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            wl_pointer::Event::Enter { serial, surface, surface_x, surface_y } => {
+                // A null cursor surface hides the cursor until the pointer leaves the overlay
+                if state.options.hide_cursor {
+                    pointer.set_cursor(serial, None, 0, 0);
+                }
+                if let Some(index) = state.outputs.iter().position(|o| o.surface.id() == surface.id()) {
+                    state.current_output = Some(index);
+                    state.focused_output = index;
+                    let output = &state.outputs[index];
+                    debug!(output = ?output.name, surface_x, surface_y, "pointer entered");
+                    state.set_cursor(to_global((surface_x, surface_y), output.logical_pos));
+                    state.draw();
+                }
+            }
+            wl_pointer::Event::Leave { .. } => {
+                debug!("pointer left");
+                state.current_output = None;
+                if state.options.output {
+                    state.draw();
+                }
+            }
+            wl_pointer::Event::Motion { surface_x, surface_y, .. } => {
+                if let Some(output_idx) = state.current_output {
+                    if let Some(output) = state.outputs.get(output_idx) {
+                         let hovered = state.hovered_box().map(|b| b.rect);
+                         state.set_cursor(to_global((surface_x, surface_y), output.logical_pos));
+                         trace!(pos = ?state.current_pos, "pointer motion");
+                         if state.follows_pointer() || state.hovered_box().map(|b| b.rect) != hovered {
+                             state.draw();
+                         }
+                    }
+                }
+            }
+            wl_pointer::Event::Button { time, button, state: btn_state, .. } => {
+                debug!(button, state = ?btn_state, pos = ?state.current_pos, "pointer button");
+                let pressed = btn_state == WEnum::Value(wl_pointer::ButtonState::Pressed);
+                if pressed && state.double_click(button, time) {
+                    state.select_hovered_box();
+                } else {
+                    state.button(button, pressed);
+                }
+            }
+            wl_pointer::Event::Axis { axis, value, .. } => {
+                trace!(?axis, value, "pointer axis");
+                state.scroll(axis == WEnum::Value(wl_pointer::Axis::HorizontalScroll), value);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlTouch, ()> for State {
+    fn event(state: &mut Self, _: &WlTouch, event: wl_touch::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            wl_touch::Event::Down { surface, id, x, y, .. } => {
+                debug!(id, x, y, "touch down");
+                // A second finger cancels the selection
+                if state.touch_point.is_some() {
+                    state.running = false;
+                    return;
+                }
+                if let Some(index) = state.outputs.iter().position(|o| o.surface.id() == surface.id()) {
+                    state.touch_point = Some((id, index));
+                    state.current_output = Some(index);
+                    state.set_cursor(to_global((x, y), state.outputs[index].logical_pos));
+                    state.press();
+                    state.draw();
+                }
+            }
+            wl_touch::Event::Motion { id, x, y, .. } => {
+                // Touch coordinates stay relative to the surface the touch started on
+                if let Some((touch_id, index)) = state.touch_point {
+                    if touch_id == id {
+                        state.set_cursor(to_global((x, y), state.outputs[index].logical_pos));
+                        state.draw();
+                    }
+                }
+            }
+            wl_touch::Event::Up { id, .. } => {
+                debug!(id, "touch up");
+                if state.touch_point.is_some_and(|(touch_id, _)| touch_id == id) {
+                    state.touch_point = None;
+                    state.release();
+                }
+            }
+            wl_touch::Event::Cancel => {
+                debug!("touch cancelled");
+                state.touch_point = None;
+                state.start_pos = None;
+                state.draw();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpKeyboardShortcutsInhibitorV1, ()> for State {
+    fn event(_: &mut Self, _: &ZwpKeyboardShortcutsInhibitorV1, event: zwp_keyboard_shortcuts_inhibitor_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            zwp_keyboard_shortcuts_inhibitor_v1::Event::Active => debug!("compositor shortcuts inhibited"),
+            zwp_keyboard_shortcuts_inhibitor_v1::Event::Inactive => debug!("compositor shortcuts restored"),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletSeatV2, ()> for State {
+    fn event(_: &mut Self, _: &ZwpTabletSeatV2, _: zwp_tablet_seat_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+
+    wayland_client::event_created_child!(State, ZwpTabletSeatV2, [
+        zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (ZwpTabletV2, ()),
+        zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (ZwpTabletToolV2, ()),
+        zwp_tablet_seat_v2::EVT_PAD_ADDED_OPCODE => (ZwpTabletPadV2, ()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletV2, ()> for State {
+    fn event(_: &mut Self, tablet: &ZwpTabletV2, event: zwp_tablet_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let zwp_tablet_v2::Event::Removed = event {
+            tablet.destroy();
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletToolV2, ()> for State {
+    fn event(state: &mut Self, tool: &ZwpTabletToolV2, event: zwp_tablet_tool_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            zwp_tablet_tool_v2::Event::ProximityIn { serial, surface, .. } => {
+                if state.options.hide_cursor {
+                    tool.set_cursor(serial, None, 0, 0);
+                }
+                state.tool_output = state.outputs.iter().position(|o| o.surface.id() == surface.id());
+                state.current_output = state.tool_output;
+            }
+            zwp_tablet_tool_v2::Event::ProximityOut => {
+                state.tool_output = None;
+            }
+            zwp_tablet_tool_v2::Event::Motion { x, y } => {
+                if let Some(index) = state.tool_output {
+                    state.set_cursor(to_global((x, y), state.outputs[index].logical_pos));
+                    state.draw();
+                }
+            }
+            // Pen tip touching the tablet acts like the left button
+            zwp_tablet_tool_v2::Event::Down { .. } => {
+                if state.tool_output.is_some() {
+                    state.button_held = true;
+                    state.press();
+                    state.draw();
+                }
+            }
+            zwp_tablet_tool_v2::Event::Up => {
+                if state.button_held {
+                    state.release();
+                    state.button_held = false;
+                }
+            }
+            // Stylus buttons go through --bind like mouse buttons
+            zwp_tablet_tool_v2::Event::Button { button, state: button_state, .. } => {
+                if state.tool_output.is_some() {
+                    state.button(button, button_state == WEnum::Value(zwp_tablet_tool_v2::ButtonState::Pressed));
+                }
+            }
+            zwp_tablet_tool_v2::Event::Removed => {
+                tool.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpTabletPadV2, ()> for State {
+    fn event(_: &mut Self, pad: &ZwpTabletPadV2, event: zwp_tablet_pad_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let zwp_tablet_pad_v2::Event::Removed = event {
+            pad.destroy();
+        }
+    }
+
+    wayland_client::event_created_child!(State, ZwpTabletPadV2, [
+        zwp_tablet_pad_v2::EVT_GROUP_OPCODE => (ZwpTabletPadGroupV2, ()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletPadGroupV2, ()> for State {
+    fn event(_: &mut Self, _: &ZwpTabletPadGroupV2, _: zwp_tablet_pad_group_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+
+    wayland_client::event_created_child!(State, ZwpTabletPadGroupV2, [
+        zwp_tablet_pad_group_v2::EVT_RING_OPCODE => (ZwpTabletPadRingV2, ()),
+        zwp_tablet_pad_group_v2::EVT_STRIP_OPCODE => (ZwpTabletPadStripV2, ()),
+    ]);
+}
+
+impl Dispatch<ZwpTabletPadRingV2, ()> for State { fn event(_: &mut Self, _: &ZwpTabletPadRingV2, _: zwp_tablet_pad_ring_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
+impl Dispatch<ZwpTabletPadStripV2, ()> for State { fn event(_: &mut Self, _: &ZwpTabletPadStripV2, _: zwp_tablet_pad_strip_v2::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {} }
@@ -0,0 +1,34 @@
+use crate::boxes::CandidateBox;
+
+// A source of window rectangles, usually a compositor IPC socket. Implement this to
+// offer windows from another compositor as selectable boxes.
+pub trait WindowProvider {
+    // Short name used in messages
+    fn name(&self) -> &'static str;
+
+    // The windows currently visible, in global logical coordinates
+    fn windows(&self) -> Result<Vec<CandidateBox>, String>;
+
+    // Called before each windows() query. Providers that cache compositor state re-read
+    // it here, the default suits providers that query on every call.
+    fn refresh(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Picks the provider of the running compositor from its environment variables
+pub fn detect() -> Option<Box<dyn WindowProvider>> {
+    #[cfg(feature = "sway")]
+    if let Some(provider) = crate::sway::Sway::from_env() {
+        return Some(Box::new(provider));
+    }
+    #[cfg(feature = "hyprland")]
+    if let Some(provider) = crate::hyprland::Hyprland::from_env() {
+        return Some(Box::new(provider));
+    }
+    #[cfg(feature = "niri")]
+    if let Some(provider) = crate::niri::Niri::from_env() {
+        return Some(Box::new(provider));
+    }
+    None
+}
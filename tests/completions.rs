@@ -0,0 +1,21 @@
+use std::process::Command;
+
+fn completions(shell: &str) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-slurp")).args(["completions", shell]).env_clear().output().unwrap()
+}
+
+#[test]
+fn completions_cover_the_flags() {
+    for shell in ["bash", "zsh", "fish"] {
+        let output = completions(shell);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("no-dim") && script.contains("renderer"), "{}", script);
+    }
+}
+
+#[test]
+fn completions_conflict_with_flags() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-slurp")).args(["-d", "completions", "bash"]).env_clear().output().unwrap();
+    assert_eq!(output.status.code(), Some(slurp::EXIT_ERROR));
+}
@@ -0,0 +1,672 @@
+// A compositor just capable enough to run rust-slurp against: it advertises wl_compositor, wl_shm,
+// wl_seat, layer-shell and xdg-output, configures the overlay surfaces with the size of their
+// output and plays back scripted input once every output shows a frame.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wayland_server::backend::ClientData;
+use wayland_server::protocol::{
+    wl_buffer::{self, WlBuffer},
+    wl_callback::{self, WlCallback},
+    wl_compositor::{self, WlCompositor},
+    wl_keyboard::{self, WlKeyboard},
+    wl_output::{self, WlOutput},
+    wl_pointer::{self, WlPointer},
+    wl_region::{self, WlRegion},
+    wl_seat::{self, WlSeat},
+    wl_shm::{self, WlShm},
+    wl_shm_pool::{self, WlShmPool},
+    wl_surface::{self, WlSurface},
+    wl_touch::{self, WlTouch},
+};
+use wayland_server::{Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, ListeningSocket, New, Resource};
+
+use wayland_protocols::xdg::xdg_output::zv1::server::{
+    zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
+use wayland_protocols_wlr::layer_shell::v1::server::{
+    zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+};
+
+use xkbcommon::xkb;
+
+pub const BTN_LEFT: u32 = 0x110;
+pub const BTN_RIGHT: u32 = 0x111;
+pub const BTN_MIDDLE: u32 = 0x112;
+pub const KEY_ESC: u32 = 1;
+pub const KEY_ENTER: u32 = 28;
+pub const KEY_SEMICOLON: u32 = 39;
+pub const KEY_RIGHT: u32 = 106;
+// Modifier mask of Shift in the default keymap
+const SHIFT_MASK: u32 = 1;
+
+// rust-slurp is killed and the test fails when it hasn't exited by then
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// An output in logical coordinates, at scale 1 unless set
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub name: String,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+    pub scale: i32,
+}
+
+impl Output {
+    pub fn new(name: &str, position: (i32, i32), size: (i32, i32)) -> Self {
+        Output { name: name.to_string(), position, size, scale: 1 }
+    }
+
+    pub fn scale(mut self, scale: i32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    fn contains(&self, (x, y): (f64, f64)) -> bool {
+        let (ox, oy) = (self.position.0 as f64, self.position.1 as f64);
+        x >= ox && y >= oy && x < ox + self.size.0 as f64 && y < oy + self.size.1 as f64
+    }
+}
+
+// Scripted input, pointer positions are global logical coordinates
+#[derive(Debug, Clone, Copy)]
+pub enum Input {
+    Move(f64, f64),
+    Press(u32),
+    Release(u32),
+    // Vertical scrolling in surface units, a wheel notch is 15
+    Scroll(f64),
+    // Pressed and released right away
+    Key(u32),
+    // Same with Shift held
+    ShiftedKey(u32),
+    // Moves the output with the index in the layout, later input waits until every output shows a frame again
+    MoveOutput(usize, (i32, i32)),
+}
+
+// Left click and drag from one point to another
+pub fn drag(from: (f64, f64), to: (f64, f64)) -> Vec<Input> {
+    vec![Input::Move(from.0, from.1), Input::Press(BTN_LEFT), Input::Move(to.0, to.1), Input::Release(BTN_LEFT)]
+}
+
+pub fn click(at: (f64, f64)) -> Vec<Input> {
+    vec![Input::Move(at.0, at.1), Input::Press(BTN_LEFT), Input::Release(BTN_LEFT)]
+}
+
+// Key presses typing the text on a US layout, only digits and the characters of a region are known
+pub fn type_text(text: &str) -> Vec<Input> {
+    text.chars()
+        .map(|c| match c {
+            '1'..='9' => Input::Key(c as u32 - '1' as u32 + 2),
+            '0' => Input::Key(11),
+            ',' => Input::Key(51),
+            ' ' => Input::Key(57),
+            'x' => Input::Key(45),
+            ':' => Input::ShiftedKey(KEY_SEMICOLON),
+            _ => panic!("can't type '{}'", c),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub struct MockCompositor {
+    outputs: Vec<Output>,
+    layer_shell: bool,
+    input: Vec<Input>,
+    stdin: Option<String>,
+    keymap: bool,
+    display_flag: bool,
+}
+
+impl Default for MockCompositor {
+    fn default() -> Self {
+        MockCompositor { outputs: vec![Output::new("DP-1", (0, 0), (1920, 1080))], layer_shell: true, input: Vec::new(), stdin: None, keymap: true, display_flag: false }
+    }
+}
+
+impl MockCompositor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn outputs(mut self, outputs: Vec<Output>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    // Neither layer-shell nor xdg-shell is advertised then
+    pub fn without_layer_shell(mut self) -> Self {
+        self.layer_shell = false;
+        self
+    }
+
+    pub fn input(mut self, input: impl IntoIterator<Item = Input>) -> Self {
+        self.input.extend(input);
+        self
+    }
+
+    pub fn stdin(mut self, stdin: &str) -> Self {
+        self.stdin = Some(stdin.to_string());
+        self
+    }
+
+    // The keyboard gets no keymap, as if the compositor's failed to load
+    pub fn without_keymap(mut self) -> Self {
+        self.keymap = false;
+        self
+    }
+
+    // Passes the socket with --display and points WAYLAND_DISPLAY at a display that doesn't exist
+    pub fn display_flag(mut self) -> Self {
+        self.display_flag = true;
+        self
+    }
+
+    // Runs rust-slurp with the arguments until it exits
+    pub fn run(self, args: &[&str]) -> Outcome {
+        let dir = runtime_dir();
+        let socket_path = dir.join("wayland-mock");
+        let socket = ListeningSocket::bind_absolute(socket_path.clone()).expect("failed to bind the mock compositor socket");
+        let mut display = Display::<Server>::new().expect("failed to create the mock display");
+        let mut handle = display.handle();
+
+        handle.create_global::<Server, WlCompositor, ()>(4, ());
+        handle.create_global::<Server, WlShm, ()>(1, ());
+        handle.create_global::<Server, WlSeat, ()>(7, ());
+        handle.create_global::<Server, ZxdgOutputManagerV1, ()>(3, ());
+        if self.layer_shell {
+            handle.create_global::<Server, ZwlrLayerShellV1, ()>(4, ());
+        }
+        for index in 0..self.outputs.len() {
+            handle.create_global::<Server, WlOutput, usize>(4, index);
+        }
+
+        let mut server = Server {
+            outputs: self.outputs,
+            surfaces: Vec::new(),
+            pointers: Vec::new(),
+            keyboards: Vec::new(),
+            keymap: if self.keymap { keymap(&dir) } else { None },
+            pointer_focus: None,
+            keyboard_focused: false,
+            xdg_outputs: Vec::new(),
+            serial: 0,
+            started: Instant::now(),
+        };
+
+        let (display_args, wayland_display) = if self.display_flag { (vec!["--display", "wayland-mock"], dir.join("wayland-missing")) } else { (Vec::new(), socket_path.clone()) };
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rust-slurp"))
+            .args(display_args)
+            .args(args)
+            .env_clear()
+            .env("WAYLAND_DISPLAY", &wayland_display)
+            .env("XDG_RUNTIME_DIR", &dir)
+            .stdin(if self.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run rust-slurp");
+        if let (Some(mut stdin), Some(text)) = (child.stdin.take(), self.stdin) {
+            stdin.write_all(text.as_bytes()).unwrap();
+        }
+
+        let mut input = VecDeque::from(self.input);
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(stream) = socket.accept().unwrap() {
+                handle.insert_client(stream, Arc::new(ClientState)).unwrap();
+            }
+            display.dispatch_clients(&mut server).unwrap();
+            if server.ready() {
+                server.focus_keyboard();
+                while let Some(event) = input.pop_front() {
+                    let waits = matches!(event, Input::MoveOutput(..));
+                    server.play(event);
+                    if waits {
+                        break;
+                    }
+                }
+            }
+            // Fails once rust-slurp is gone, which the exit status below reports
+            let _ = display.flush_clients();
+            if child.try_wait().unwrap().is_some() {
+                break;
+            }
+            if Instant::now() > deadline {
+                let _ = child.kill();
+                let output = child.wait_with_output().unwrap();
+                panic!("rust-slurp didn't exit, stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        let output = child.wait_with_output().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        Outcome {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+fn runtime_dir() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!("rust-slurp-test-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// The default xkb keymap as the compositor would send it, None without xkeyboard-config installed
+fn keymap(dir: &std::path::Path) -> Option<(File, u32)> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb::Keymap::new_from_names(&context, "", "", "", "", None, xkb::KEYMAP_COMPILE_NO_FLAGS)?;
+    let mut text = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1).into_bytes();
+    text.push(0);
+    let path = dir.join("keymap");
+    let mut file = File::create(&path).unwrap();
+    file.write_all(&text).unwrap();
+    Some((File::open(&path).unwrap(), text.len() as u32))
+}
+
+struct ClientState;
+
+impl ClientData for ClientState {}
+
+struct Surface {
+    surface: WlSurface,
+    layer_surface: Option<ZwlrLayerSurfaceV1>,
+    output: usize,
+    configured: bool,
+    // A buffer was committed after the configure
+    mapped: bool,
+    pending_buffer: Option<WlBuffer>,
+    frame_callbacks: Vec<WlCallback>,
+}
+
+struct Server {
+    outputs: Vec<Output>,
+    surfaces: Vec<Surface>,
+    pointers: Vec<WlPointer>,
+    keyboards: Vec<WlKeyboard>,
+    keymap: Option<(File, u32)>,
+    pointer_focus: Option<usize>,
+    keyboard_focused: bool,
+    // The xdg-output of each output along with the wl_output it was created for
+    xdg_outputs: Vec<(ZxdgOutputV1, WlOutput, usize)>,
+    serial: u32,
+    started: Instant,
+}
+
+impl Server {
+    fn next_serial(&mut self) -> u32 {
+        self.serial += 1;
+        self.serial
+    }
+
+    fn time(&self) -> u32 {
+        self.started.elapsed().as_millis() as u32
+    }
+
+    fn surface_mut(&mut self, surface: &WlSurface) -> Option<&mut Surface> {
+        self.surfaces.iter_mut().find(|s| s.surface == *surface)
+    }
+
+    fn overlay_on(&self, output: usize) -> Option<&WlSurface> {
+        self.surfaces.iter().find(|s| s.layer_surface.is_some() && s.output == output).map(|s| &s.surface)
+    }
+
+    // Every output shows a frame, possibly through a mirror, and the pointer exists
+    fn ready(&self) -> bool {
+        let same_area = |a: &Output, b: &Output| a.position == b.position && a.size == b.size;
+        let shown = self.outputs.iter().all(|o| self.surfaces.iter().any(|s| same_area(&self.outputs[s.output], o) && s.layer_surface.is_some() && s.mapped));
+        shown && !self.pointers.is_empty()
+    }
+
+    fn focus_keyboard(&mut self) {
+        if self.keyboard_focused {
+            return;
+        }
+        let Some(surface) = self.overlay_on(0).cloned() else {
+            return;
+        };
+        let serial = self.next_serial();
+        for keyboard in &self.keyboards {
+            keyboard.enter(serial, &surface, Vec::new());
+            keyboard.modifiers(serial, 0, 0, 0, 0);
+        }
+        self.keyboard_focused = true;
+    }
+
+    fn play(&mut self, input: Input) {
+        let time = self.time();
+        let serial = self.next_serial();
+        match input {
+            Input::Move(x, y) => {
+                let Some(index) = self.outputs.iter().position(|o| o.contains((x, y))) else {
+                    panic!("({}, {}) isn't on any output", x, y);
+                };
+                let Some(surface) = self.overlay_on(index).cloned() else {
+                    return;
+                };
+                let previous = self.pointer_focus.and_then(|i| self.overlay_on(i)).cloned();
+                let (local_x, local_y) = (x - self.outputs[index].position.0 as f64, y - self.outputs[index].position.1 as f64);
+                for pointer in &self.pointers {
+                    if self.pointer_focus == Some(index) {
+                        pointer.motion(time, local_x, local_y);
+                    } else {
+                        if let Some(previous) = &previous {
+                            pointer.leave(serial, previous);
+                        }
+                        pointer.enter(serial, &surface, local_x, local_y);
+                    }
+                    if pointer.version() >= 5 {
+                        pointer.frame();
+                    }
+                }
+                self.pointer_focus = Some(index);
+            }
+            Input::Press(button) | Input::Release(button) => {
+                let state = if matches!(input, Input::Press(_)) { wl_pointer::ButtonState::Pressed } else { wl_pointer::ButtonState::Released };
+                for pointer in &self.pointers {
+                    pointer.button(serial, time, button, state);
+                    if pointer.version() >= 5 {
+                        pointer.frame();
+                    }
+                }
+            }
+            Input::Scroll(value) => {
+                for pointer in &self.pointers {
+                    pointer.axis(time, wl_pointer::Axis::VerticalScroll, value);
+                    if pointer.version() >= 5 {
+                        pointer.frame();
+                    }
+                }
+            }
+            Input::Key(key) => {
+                for keyboard in &self.keyboards {
+                    keyboard.key(serial, time, key, wl_keyboard::KeyState::Pressed);
+                    keyboard.key(serial, time, key, wl_keyboard::KeyState::Released);
+                }
+            }
+            Input::ShiftedKey(key) => {
+                for keyboard in &self.keyboards {
+                    keyboard.modifiers(serial, SHIFT_MASK, 0, 0, 0);
+                    keyboard.key(serial, time, key, wl_keyboard::KeyState::Pressed);
+                    keyboard.key(serial, time, key, wl_keyboard::KeyState::Released);
+                    keyboard.modifiers(serial, 0, 0, 0, 0);
+                }
+            }
+            Input::MoveOutput(index, position) => {
+                self.outputs[index].position = position;
+                for (xdg_output, output, _) in self.xdg_outputs.iter().filter(|(_, _, i)| *i == index) {
+                    xdg_output.logical_position(position.0, position.1);
+                    if xdg_output.version() >= 3 {
+                        output.done();
+                    } else {
+                        xdg_output.done();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl GlobalDispatch<WlCompositor, ()> for Server {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<WlCompositor>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<WlCompositor, ()> for Server {
+    fn request(state: &mut Self, _: &Client, _: &WlCompositor, request: wl_compositor::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                let surface = data_init.init(id, ());
+                state.surfaces.push(Surface { surface, layer_surface: None, output: 0, configured: false, mapped: false, pending_buffer: None, frame_callbacks: Vec::new() });
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlRegion, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlRegion, _: wl_region::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl Dispatch<WlSurface, ()> for Server {
+    fn request(state: &mut Self, _: &Client, resource: &WlSurface, request: wl_surface::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                if let Some(surface) = state.surface_mut(resource) {
+                    surface.pending_buffer = buffer;
+                }
+            }
+            wl_surface::Request::Frame { callback } => {
+                let callback = data_init.init(callback, ());
+                if let Some(surface) = state.surface_mut(resource) {
+                    surface.frame_callbacks.push(callback);
+                }
+            }
+            wl_surface::Request::Commit => {
+                let serial = state.next_serial();
+                let time = state.time();
+                let outputs = state.outputs.clone();
+                let Some(surface) = state.surface_mut(resource) else {
+                    return;
+                };
+                match (&surface.layer_surface, surface.configured) {
+                    // The initial commit is answered with the output's size
+                    (Some(layer_surface), false) => {
+                        let (width, height) = outputs[surface.output].size;
+                        layer_surface.configure(serial, width as u32, height as u32);
+                        surface.configured = true;
+                    }
+                    // Nothing reads the pixels, so buffers are released right away
+                    (Some(_), true) => {
+                        if let Some(buffer) = surface.pending_buffer.take() {
+                            buffer.release();
+                            surface.mapped = true;
+                        }
+                    }
+                    (None, _) => {}
+                }
+                for callback in surface.frame_callbacks.drain(..) {
+                    callback.done(time);
+                }
+            }
+            wl_surface::Request::Destroy => {
+                state.surfaces.retain(|s| s.surface != *resource);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlCallback, _: wl_callback::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl GlobalDispatch<WlShm, ()> for Server {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<WlShm>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+        shm.format(wl_shm::Format::Xrgb8888);
+    }
+}
+
+impl Dispatch<WlShm, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlShm, request: wl_shm::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        if let wl_shm::Request::CreatePool { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlShmPool, request: wl_shm_pool::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        if let wl_shm_pool::Request::CreateBuffer { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlBuffer, _: wl_buffer::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl GlobalDispatch<WlOutput, usize> for Server {
+    fn bind(state: &mut Self, _: &DisplayHandle, _: &Client, resource: New<WlOutput>, index: &usize, data_init: &mut DataInit<'_, Self>) {
+        let output = data_init.init(resource, *index);
+        let config = &state.outputs[*index];
+        output.geometry(config.position.0, config.position.1, 0, 0, wl_output::Subpixel::Unknown, "mock".to_string(), config.name.clone(), wl_output::Transform::Normal);
+        output.mode(wl_output::Mode::Current | wl_output::Mode::Preferred, config.size.0 * config.scale, config.size.1 * config.scale, 60000);
+        if output.version() >= 2 {
+            output.scale(config.scale);
+        }
+        if output.version() >= 4 {
+            output.name(config.name.clone());
+            output.description(format!("Mock output {}", config.name));
+        }
+        if output.version() >= 2 {
+            output.done();
+        }
+    }
+}
+
+impl Dispatch<WlOutput, usize> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlOutput, _: wl_output::Request, _: &usize, _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl GlobalDispatch<ZxdgOutputManagerV1, ()> for Server {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<ZxdgOutputManagerV1>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZxdgOutputManagerV1, ()> for Server {
+    fn request(state: &mut Self, _: &Client, _: &ZxdgOutputManagerV1, request: zxdg_output_manager_v1::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        if let zxdg_output_manager_v1::Request::GetXdgOutput { id, output } = request {
+            let xdg_output = data_init.init(id, ());
+            let Some(&index) = output.data::<usize>() else {
+                return;
+            };
+            state.xdg_outputs.push((xdg_output.clone(), output.clone(), index));
+            let config = &state.outputs[index];
+            xdg_output.logical_position(config.position.0, config.position.1);
+            xdg_output.logical_size(config.size.0, config.size.1);
+            if xdg_output.version() >= 2 {
+                xdg_output.name(config.name.clone());
+                xdg_output.description(format!("Mock output {}", config.name));
+            }
+            // Since version 3 wl_output.done ends the update for both
+            if xdg_output.version() >= 3 {
+                output.done();
+            } else {
+                xdg_output.done();
+            }
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &ZxdgOutputV1, _: zxdg_output_v1::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl GlobalDispatch<ZwlrLayerShellV1, ()> for Server {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<ZwlrLayerShellV1>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrLayerShellV1, ()> for Server {
+    fn request(state: &mut Self, _: &Client, _: &ZwlrLayerShellV1, request: zwlr_layer_shell_v1::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        if let zwlr_layer_shell_v1::Request::GetLayerSurface { id, surface, output, .. } = request {
+            let layer_surface = data_init.init(id, ());
+            let output = output.and_then(|o| o.data::<usize>().copied()).unwrap_or(0);
+            if let Some(surface) = state.surface_mut(&surface) {
+                surface.layer_surface = Some(layer_surface);
+                surface.output = output;
+            }
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for Server {
+    fn request(state: &mut Self, _: &Client, resource: &ZwlrLayerSurfaceV1, request: zwlr_layer_surface_v1::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {
+        if let zwlr_layer_surface_v1::Request::Destroy = request {
+            for surface in state.surfaces.iter_mut().filter(|s| s.layer_surface.as_ref() == Some(resource)) {
+                surface.layer_surface = None;
+                surface.mapped = false;
+            }
+        }
+    }
+}
+
+impl GlobalDispatch<WlSeat, ()> for Server {
+    fn bind(_: &mut Self, _: &DisplayHandle, _: &Client, resource: New<WlSeat>, _: &(), data_init: &mut DataInit<'_, Self>) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(wl_seat::Capability::Pointer | wl_seat::Capability::Keyboard);
+        if seat.version() >= 2 {
+            seat.name("seat0".to_string());
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for Server {
+    fn request(state: &mut Self, _: &Client, _: &WlSeat, request: wl_seat::Request, _: &(), _: &DisplayHandle, data_init: &mut DataInit<'_, Self>) {
+        match request {
+            wl_seat::Request::GetPointer { id } => {
+                let pointer = data_init.init(id, ());
+                state.pointers.push(pointer);
+            }
+            wl_seat::Request::GetKeyboard { id } => {
+                let keyboard = data_init.init(id, ());
+                match &state.keymap {
+                    Some((file, size)) => keyboard.keymap(wl_keyboard::KeymapFormat::XkbV1, file.as_fd(), *size),
+                    None => eprintln!("no xkb keymap available, only Escape and Enter will work"),
+                }
+                if keyboard.version() >= 4 {
+                    keyboard.repeat_info(25, 600);
+                }
+                state.keyboards.push(keyboard);
+            }
+            wl_seat::Request::GetTouch { id } => {
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlPointer, _: wl_pointer::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl Dispatch<WlKeyboard, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlKeyboard, _: wl_keyboard::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
+
+impl Dispatch<WlTouch, ()> for Server {
+    fn request(_: &mut Self, _: &Client, _: &WlTouch, _: wl_touch::Request, _: &(), _: &DisplayHandle, _: &mut DataInit<'_, Self>) {}
+}
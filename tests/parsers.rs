@@ -0,0 +1,139 @@
+use proptest::prelude::*;
+use slurp::boxes::{parse_box, read_boxes, CandidateBox};
+use slurp::color::Color;
+use slurp::format::{self, FormatContext, FormatOptions, DEFAULT_FORMAT, DEFAULT_LABELED_FORMAT};
+
+fn context<'a>(
+    rect: (i32, i32, i32, i32),
+    output: (i32, i32, f64),
+    label: Option<&'a str>,
+) -> FormatContext<'a> {
+    FormatContext {
+        x: rect.0,
+        y: rect.1,
+        width: rect.2,
+        height: rect.3,
+        output_x: output.0,
+        output_y: output.1,
+        output_scale: output.2,
+        output_name: Some("DP-1"),
+        label,
+        app_id: None,
+        title: None,
+    }
+}
+
+// Labels survive a round trip as long as they have no surrounding whitespace or line breaks
+fn label() -> impl Strategy<Value = String> {
+    "[^\\s](.*[^\\s])?"
+}
+
+proptest! {
+    #[test]
+    fn format_never_panics(format in ".*", x: f64, y: f64, w: f64, h: f64, float: bool, pixels: bool, ox: i32, oy: i32, scale: f64, label in ".*") {
+        let tokens = format::parse(&format);
+        let ctx = context((x as i32, y as i32, w as i32, h as i32), (ox, oy, scale), Some(&label));
+        format::expand(&tokens, &ctx);
+        format::expand_with(&tokens, &ctx, (x, y, w, h), FormatOptions { float, pixels });
+        format::expand_for_shell(&tokens);
+        format::shell_variables(&ctx);
+        format::shell_variables_with(&ctx, (x, y, w, h), FormatOptions { float, pixels });
+    }
+
+    #[test]
+    fn format_round_trips(format in ".*") {
+        let tokens = format::parse(&format);
+        prop_assert_eq!(format::parse(&format::unparse(&tokens)), tokens);
+    }
+
+    #[test]
+    fn plain_text_expands_to_itself(text in "[^%]*", x: i32, y: i32) {
+        let ctx = context((x, y, 1, 1), (0, 0, 1.0), None);
+        prop_assert_eq!(format::expand(&format::parse(&text), &ctx), text);
+    }
+
+    #[test]
+    fn escaped_percent_is_literal(text in "[^%]*", x: i32, y: i32) {
+        let ctx = context((x, y, 1, 1), (0, 0, 1.0), None);
+        prop_assert_eq!(format::expand(&format::parse(&format!("%%{}%%", text)), &ctx), format!("%{}%", text));
+    }
+
+    #[test]
+    fn box_parser_never_panics(line in ".*") {
+        let _ = parse_box(&line);
+    }
+
+    #[test]
+    fn box_reader_never_panics(input: Vec<u8>) {
+        read_boxes(input.as_slice());
+    }
+
+    #[test]
+    fn default_format_round_trips(x: i32, y: i32, w in 1..=i32::MAX, h in 1..=i32::MAX) {
+        let ctx = context((x, y, w, h), (0, 0, 1.0), None);
+        let parsed = parse_box(&format::expand(&format::parse(DEFAULT_FORMAT), &ctx)).unwrap();
+        prop_assert_eq!(parsed, CandidateBox { rect: (x as f64, y as f64, w as f64, h as f64), label: None, app_id: None, title: None });
+    }
+
+    #[test]
+    fn labeled_format_round_trips(x: i32, y: i32, w in 1..=i32::MAX, h in 1..=i32::MAX, label in proptest::option::of(label())) {
+        let ctx = context((x, y, w, h), (0, 0, 1.0), label.as_deref());
+        let line = format::expand(&format::parse(DEFAULT_LABELED_FORMAT), &ctx);
+        let parsed = parse_box(&line).unwrap();
+        prop_assert_eq!(parsed.rect, (x as f64, y as f64, w as f64, h as f64));
+        prop_assert_eq!(parsed.label, label);
+        prop_assert_eq!(parse_box(&line).unwrap(), parsed);
+    }
+
+    #[test]
+    fn nonpositive_sizes_are_rejected(x: i32, y: i32, w in i32::MIN..=0, h: i32) {
+        prop_assert!(parse_box(&format!("{},{} {}x{}", x, y, w, h)).is_err());
+        prop_assert!(parse_box(&format!("{},{} {}x{}", x, y, h, w)).is_err());
+    }
+
+    #[test]
+    fn float_output_is_unrounded(x in -1e6..1e6f64, y in -1e6..1e6f64, w in 0.0..1e6f64, h in 0.0..1e6f64) {
+        let ctx = context((0, 0, 0, 0), (0, 0, 1.0), None);
+        let fields: Vec<f64> = format::expand_with(&format::parse("%x %y %w %h"), &ctx, (x, y, w, h), FormatOptions { float: true, ..Default::default() }).split(' ').map(|n| n.parse().unwrap()).collect();
+        prop_assert_eq!(fields, vec![x, y, w, h]);
+    }
+}
+
+#[test]
+fn output_themes_are_parsed() {
+    let options = slurp::cli::try_parse_from(["--output-theme", "DP-1:dim-opacity=0.2,border=#ffffff", "--output-theme", "HDMI-A-1:box=#ff000080"]).unwrap();
+    let (name, theme) = &options.output_themes[0];
+    assert_eq!(name, "DP-1");
+    assert_eq!(theme.dim_opacity, Some(0.2));
+    assert_eq!(theme.border_color, Some(Color::new(1.0, 1.0, 1.0, 1.0)));
+    assert_eq!(options.output_themes[1].1.box_color.map(|c| c.r), Some(1.0));
+
+    for theme in ["DP-1", "DP-1:dim=0.2", "DP-1:dim-opacity=2", "DP-1:border=white"] {
+        assert!(slurp::cli::try_parse_from(["--output-theme", theme]).is_err(), "{}", theme);
+    }
+}
+
+#[test]
+fn snap_distances_are_validated() {
+    assert_eq!(slurp::cli::try_parse_from(["--snap-edges", "0"]).unwrap().snap_edges, Some(0.0));
+    assert_eq!(slurp::cli::try_parse_from(["--snap-edges", "2.5"]).unwrap().snap_edges, Some(2.5));
+    for distance in ["-1", "NaN", "inf", "ten"] {
+        assert!(slurp::cli::try_parse_from(["--snap-edges", distance]).is_err(), "{}", distance);
+    }
+}
+
+#[test]
+fn button_codes_are_decimal_or_hex() {
+    let options = slurp::cli::try_parse_from(["--bind", "0x113=cancel,276=confirm"]).unwrap();
+    assert!(options.button_bindings.contains(&(0x113, slurp::ButtonAction::Cancel)));
+    assert!(options.button_bindings.contains(&(0x114, slurp::ButtonAction::Confirm)));
+    assert!(slurp::cli::try_parse_from(["--bind", "0xg=cancel"]).is_err());
+}
+
+#[test]
+fn shell_references_leave_single_quotes() {
+    let expand = |command: &str| format::expand_for_shell(&format::parse(command));
+    assert_eq!(expand("echo %x \"%y\""), "echo ${SLURP_X} \"${SLURP_Y}\"");
+    assert_eq!(expand("notify-send '%g'"), "notify-send ''\"${SLURP_GEOMETRY}\"''");
+    assert_eq!(expand("echo \"it's %w\" \\'%h"), "echo \"it's ${SLURP_WIDTH}\" \\'${SLURP_HEIGHT}");
+}
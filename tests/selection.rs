@@ -0,0 +1,322 @@
+mod mock;
+
+use mock::{click, drag, type_text, Input, MockCompositor, Output, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, KEY_ENTER, KEY_ESC, KEY_RIGHT};
+
+#[test]
+fn drag_prints_the_region() {
+    let outcome = MockCompositor::new().input(drag((10.0, 20.0), (110.0, 70.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,20 100x50\n");
+}
+
+#[test]
+fn drag_towards_the_origin() {
+    let outcome = MockCompositor::new().input(drag((300.0, 200.0), (100.0, 150.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,150 200x50\n");
+}
+
+#[test]
+fn output_left_of_and_above_the_origin() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (-1280, -1024), (1280, 1024))];
+    let outcome = MockCompositor::new().outputs(outputs).input(drag((-1000.0, -900.0), (-800.0, -850.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "-1000,-900 200x50\n");
+}
+
+#[test]
+fn drag_across_outputs() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (1920, 1080))];
+    let outcome = MockCompositor::new().outputs(outputs).input(drag((1800.0, 100.0), (2000.0, 300.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1800,100 200x200\n");
+}
+
+#[test]
+fn custom_format() {
+    let outcome = MockCompositor::new().input(drag((10.0, 20.0), (40.0, 60.0))).run(&["-f", "%x %y %w %h %o"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10 20 30 40 DP-1\n");
+}
+
+#[test]
+fn point_mode_selects_one_pixel() {
+    let outcome = MockCompositor::new().input(click((640.0, 480.0))).run(&["-p"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "640,480 1x1\n");
+}
+
+#[test]
+fn output_mode_selects_the_output_under_the_pointer() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (2560, 1440))];
+    let outcome = MockCompositor::new().outputs(outputs).input(click((2500.0, 700.0))).run(&["-o"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1920,0 2560x1440\n");
+}
+
+#[test]
+fn restrict_picks_the_box_under_the_pointer() {
+    let outcome = MockCompositor::new().stdin("0,0 100x100\n200,300 50x60\n").input(click((210.0, 320.0))).run(&["-r"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "200,300 50x60\n");
+}
+
+#[test]
+fn right_click_cancels() {
+    let outcome = MockCompositor::new().input([Input::Move(100.0, 100.0), Input::Press(BTN_RIGHT), Input::Release(BTN_RIGHT)]).run(&[]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[test]
+fn escape_cancels() {
+    let outcome = MockCompositor::new().input([Input::Move(100.0, 100.0), Input::Key(KEY_ESC)]).run(&[]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[test]
+fn click_without_drag_cancels() {
+    let outcome = MockCompositor::new().input(click((100.0, 100.0))).run(&[]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[test]
+fn missing_shell_is_unsupported() {
+    let outcome = MockCompositor::new().without_layer_shell().run(&[]);
+    assert_eq!(outcome.code, Some(2), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("zwlr_layer_shell_v1"), "{}", outcome.stderr);
+}
+
+#[test]
+fn drag_below_min_size_is_rejected() {
+    let input = [drag((10.0, 10.0), (30.0, 30.0)), drag((10.0, 10.0), (110.0, 60.0))].concat();
+    let outcome = MockCompositor::new().input(input).run(&["--min-size", "50x50"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,10 100x50\n");
+}
+
+#[test]
+fn pixels_are_relative_to_the_scaled_output() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (1280, 720)).scale(2)];
+    let outcome = MockCompositor::new().outputs(outputs).input(drag((2000.0, 100.0), (2100.0, 150.0))).run(&["--pixels"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "160,200 200x100\n");
+}
+
+#[test]
+fn single_output_clips_the_drag() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (1920, 1080))];
+    let outcome = MockCompositor::new().outputs(outputs).input(drag((1800.0, 100.0), (2000.0, 300.0))).run(&["--single-output"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1800,100 120x200\n");
+}
+
+#[test]
+fn selection_stays_on_the_outputs() {
+    let outcome = MockCompositor::new().input(drag((100.0, 1000.0), (300.0, 1050.0))).run(&["-a", "1:1"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,1000 200x80\n");
+}
+
+#[test]
+fn mirrored_outputs_share_one_overlay() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("HDMI-A-1", (0, 0), (1920, 1080))];
+    let outcome = MockCompositor::new().outputs(outputs).input(drag((10.0, 20.0), (110.0, 70.0))).run(&["-f", "%g %o", "-v"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,20 100x50 DP-1\n");
+    assert!(outcome.stderr.contains("output mirrors another one"), "{}", outcome.stderr);
+}
+
+#[test]
+fn outputs_that_stop_mirroring_get_an_overlay() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("HDMI-A-1", (0, 0), (1920, 1080))];
+    let input = [vec![Input::MoveOutput(1, (1920, 0))], drag((2000.0, 100.0), (2100.0, 150.0))].concat();
+    let outcome = MockCompositor::new().outputs(outputs).input(input).run(&["-f", "%g %o", "-v"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "2000,100 100x50 HDMI-A-1\n");
+    assert!(outcome.stderr.contains("output stopped mirroring"), "{}", outcome.stderr);
+}
+
+#[test]
+fn unknown_outputs_are_an_error() {
+    let outcome = MockCompositor::new().run(&["--outputs", "HDMI-A-1,DP-2"]);
+    assert_eq!(outcome.code, Some(3), "{}", outcome.stderr);
+    assert!(outcome.stderr.contains("none of the outputs HDMI-A-1,DP-2 exist"), "{}", outcome.stderr);
+}
+
+#[test]
+fn dragging_from_inside_moves_the_selection() {
+    let input = [drag((100.0, 100.0), (300.0, 200.0)), drag((150.0, 150.0), (250.0, 170.0)), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&["--edit"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "200,120 200x100\n");
+}
+
+#[test]
+fn rebound_buttons() {
+    let input = [Input::Move(10.0, 20.0), Input::Press(BTN_RIGHT), Input::Move(110.0, 70.0), Input::Release(BTN_RIGHT)];
+    let outcome = MockCompositor::new().input(input).run(&["--bind", "right=start-selection,left=cancel"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,20 100x50\n");
+
+    let outcome = MockCompositor::new().input(click((100.0, 100.0))).run(&["--bind", "left=cancel"]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+}
+
+#[test]
+fn middle_click_picks_the_box_or_output() {
+    let middle_click = |at: (f64, f64)| [Input::Move(at.0, at.1), Input::Press(BTN_MIDDLE), Input::Release(BTN_MIDDLE)];
+    let outcome = MockCompositor::new().stdin("200,300 50x60\n").input(middle_click((210.0, 320.0))).run(&["-r"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "200,300 50x60\n");
+
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (2560, 1440))];
+    let outcome = MockCompositor::new().outputs(outputs).input(middle_click((2500.0, 700.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1920,0 2560x1440\n");
+}
+
+#[test]
+fn scrolling_grows_the_kept_selection() {
+    let input = [drag((100.0, 100.0), (300.0, 200.0)), vec![Input::Scroll(-30.0), Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&["--edit", "--scroll-step", "10"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "90,90 220x120\n");
+}
+
+#[test]
+fn double_click_selects_the_box() {
+    let double_click = |at: (f64, f64)| [click(at), click(at)].concat();
+    // In edit mode the box is kept for adjusting until Enter
+    let input = [double_click((210.0, 320.0)), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().stdin("200,300 50x60\n").input(input).run(&["--snap-edges", "0", "--edit"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "200,300 50x60\n");
+
+    let input = [double_click((210.0, 320.0)), double_click((410.0, 120.0)), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().stdin("200,300 50x60\n400,100 30x40\n").input(input).run(&["--snap-edges", "0", "--multiple"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "200,300 50x60\n400,100 30x40\n");
+}
+
+#[test]
+fn typing_a_region_into_the_prompt() {
+    let input = [type_text(":100,200 300x150"), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,200 300x150\n");
+
+    // Just a size starts at the cursor, invalid text is kept for fixing
+    let input = [vec![Input::Move(50.0, 60.0)], type_text(":30x"), vec![Input::Key(KEY_ENTER)], type_text("40"), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "50,60 30x40\n");
+}
+
+#[test]
+fn typed_regions_follow_the_drag_rules() {
+    let outputs = vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (1920, 1080))];
+    let input = [type_text(":1800,100 300x100"), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().outputs(outputs).input(input).run(&["--single-output"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1800,100 120x100\n");
+
+    // The rejected text stays in the prompt, the first Escape closes it and the second cancels
+    let input = [type_text(":100,100 50x50"), vec![Input::Key(KEY_ENTER), Input::Key(KEY_ESC), Input::Key(KEY_ESC)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&["--min-size", "100x100"]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[test]
+fn reports_the_output_holding_most_of_the_selection() {
+    let outputs = || vec![Output::new("DP-1", (0, 0), (1920, 1080)), Output::new("DP-2", (1920, 0), (2560, 1440))];
+    let outcome = MockCompositor::new().outputs(outputs()).input(drag((1800.0, 100.0), (2400.0, 300.0))).run(&["--print-output"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "1800,100 600x200 DP-2\n");
+
+    let outcome = MockCompositor::new().outputs(outputs()).input(drag((1000.0, 100.0), (2000.0, 300.0))).run(&["-f", "%o %X,%Y"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "DP-1 1000,100\n");
+}
+
+#[test]
+fn exec_placeholders_expand_inside_quotes() {
+    let outcome = MockCompositor::new().input(drag((100.0, 100.0), (300.0, 200.0))).run(&["--exec", "echo %x \"%wx%h\" 'at %g' '$HOME'"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100 200x100 at 100,100 200x100 $HOME\n");
+}
+
+#[test]
+fn live_output_ends_with_the_final_region() {
+    let input = vec![Input::Move(100.0, 100.0), Input::Press(BTN_LEFT), Input::Move(150.0, 150.0), Input::Move(200.0, 180.0), Input::Move(300.0, 200.0), Input::Release(BTN_LEFT)];
+    let outcome = MockCompositor::new().input(input).run(&["--live", "-f", "%x,%y %wx%h %o"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    // Updates within the throttle interval are dropped, except for the last one
+    assert!(outcome.stdout.ends_with("100,100 200x100 DP-1\ndone\n100,100 200x100 DP-1\n"), "{}", outcome.stdout);
+}
+
+#[test]
+fn gpu_renderer_selects_or_falls_back_to_the_cpu() {
+    // Without the gpu feature or an EGL driver for the mock compositor the CPU draws instead
+    let outcome = MockCompositor::new().input(drag((100.0, 100.0), (300.0, 200.0))).run(&["--renderer", "gpu"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,100 200x100\n");
+}
+
+#[test]
+fn display_flag_overrides_wayland_display() {
+    let outcome = MockCompositor::new().display_flag().input(drag((100.0, 100.0), (300.0, 200.0))).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,100 200x100\n");
+}
+
+#[test]
+fn escape_and_enter_work_without_a_keymap() {
+    let outcome = MockCompositor::new().without_keymap().input([Input::Move(100.0, 100.0), Input::Key(KEY_ESC)]).run(&[]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+
+    let input = [drag((100.0, 100.0), (300.0, 200.0)), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().without_keymap().input(input).run(&["--edit"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,100 200x100\n");
+}
+
+#[test]
+fn click_on_a_snap_target_cancels() {
+    let outcome = MockCompositor::new().stdin("200,300 50x60\n").input(click((210.0, 320.0))).run(&["--snap-edges", "10"]);
+    assert_eq!(outcome.code, Some(1), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "");
+}
+
+#[test]
+fn keyboard_resize_survives_pointer_motion() {
+    let input = [Input::Move(100.0, 100.0), Input::Press(BTN_LEFT), Input::Move(200.0, 200.0), Input::ShiftedKey(KEY_RIGHT), Input::Move(300.0, 200.0), Input::Release(BTN_LEFT)];
+    let outcome = MockCompositor::new().input(input).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "100,100 210x100\n");
+}
+
+#[test]
+fn scrolled_selection_survives_pointer_motion() {
+    let input = [Input::Move(100.0, 100.0), Input::Press(BTN_LEFT), Input::Move(300.0, 200.0), Input::Scroll(-30.0), Input::Move(310.0, 210.0), Input::Release(BTN_LEFT)];
+    let outcome = MockCompositor::new().input(input).run(&["--scroll-step", "10"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "90,90 230x130\n");
+}
+
+#[test]
+fn too_small_regions_are_rejected_everywhere() {
+    // A drag collapsed to a line is rejected rather than taken as a cancel
+    let input = [drag((10.0, 10.0), (110.0, 10.0)), drag((10.0, 10.0), (110.0, 60.0))].concat();
+    let outcome = MockCompositor::new().input(input).run(&[]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,10 100x50\n");
+
+    let input = [vec![Input::Move(10.0, 20.0)], type_text(":60x6"), vec![Input::Key(KEY_ENTER)], type_text("0"), vec![Input::Key(KEY_ENTER)]].concat();
+    let outcome = MockCompositor::new().input(input).run(&["--min-size", "50x50"]);
+    assert_eq!(outcome.code, Some(0), "{}", outcome.stderr);
+    assert_eq!(outcome.stdout, "10,20 60x60\n");
+}